@@ -0,0 +1,27 @@
+//! Finding the largest entries in a tree
+
+use crate::inspector::SizeMode;
+use crate::tree::{NodeId, Tree, ROOT};
+
+/// Returns the `count` largest items exactly `depth` levels below the root, largest first.
+/// Extra links are left out as their size is already counted where the file was first found
+pub fn largest_at_depth(tree: &Tree, depth: usize, count: usize, size_mode: SizeMode) -> Vec<NodeId> {
+    let mut items = vec![];
+    collect_at_depth(tree, ROOT, depth, &mut items);
+    items.sort_by(|lhs, rhs| tree.size_for(*rhs, size_mode).cmp(&tree.size_for(*lhs, size_mode)).then(tree.path(*lhs).cmp(&tree.path(*rhs))));
+    items.truncate(count);
+    return items;
+}
+
+fn collect_at_depth(tree: &Tree, id: NodeId, depth: usize, items: &mut Vec<NodeId>) {
+    if tree.item(id).is_extra_link {
+        return;
+    }
+    if depth == 0 {
+        items.push(id);
+        return;
+    }
+    for child in tree.children(id) {
+        collect_at_depth(tree, *child, depth - 1, items);
+    }
+}
@@ -1,10 +1,23 @@
 use sysinfo::SystemExt;
-use crate::inspector::{*};
+use disk_inspector::inspector::{*};
 use std::io::{Error, ErrorKind};
 use std::thread;
 use std::thread::JoinHandle;
-use crate::atomic_counter::AtomicCounter;
-use std::sync::Arc;
+use disk_inspector::cancel_token::CancelToken;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Sender};
+use std::path::PathBuf;
+use disk_inspector::scan_options::ScanOptions;
+use disk_inspector::tree::Tree;
+
+/// A read running on a background thread, `live` can be used to see what has been read so far
+pub struct Scan {
+    pub thread: JoinHandle<Tree>,
+    pub live: Arc<LiveScan>,
+    pub progress: Arc<Mutex<ScanProgress>>,
+    /// Stops the read, the thread then returns what had been read
+    pub cancel_token: Arc<CancelToken>,
+}
 
 pub struct App {}
 
@@ -27,39 +40,101 @@ impl App {
         return Ok(disks);
     }
 
-    pub fn read_file_sizes(&self, selected_disk: &mut Disk, progress_counter: Arc<AtomicCounter>) -> JoinHandle<Option<DiskItem>> {
-        let name = selected_disk.name.clone();
+    /// Creates a disk for scanning `path`, the space figures are taken from the disk it is on
+    pub fn disk_for_path(&self, path: PathBuf) -> Result<Disk, std::io::Error> {
+        let path = path.canonicalize()?;
+        if !path.is_dir() {
+            return Err(Error::new(ErrorKind::InvalidInput, format!("{} is not a directory", path.display())));
+        }
+
+        let mut system = sysinfo::System::new();
+        system.refresh_all();
+        let containing_disk = get_all_disks(system)
+            .into_iter()
+            .filter(|disk| path.starts_with(disk.path()))
+            .max_by_key(|disk| disk.path().components().count());
+
+        let (available_space, total_space) = containing_disk
+            .map(|disk| (disk.available_space, disk.total_space))
+            .unwrap_or((0, 0));
+
+        return Ok(Disk {
+            name: path.to_string_lossy().to_string(),
+            available_space,
+            total_space,
+            tree: Arc::new(Tree::new_root(&path)),
+        });
+    }
+
+    pub fn read_file_sizes(&self, selected_disk: &mut Disk, options: ScanOptions) -> Scan {
         let total_space = selected_disk.total_space;
         let available_space = selected_disk.available_space;
         let available_size = total_space - available_space;
-        let disk_path = selected_disk.root.path.clone();
-        return thread::spawn(move || {
-            let mut inspector = Inspector::new(available_size, |status| {
+        let cancel_token = Arc::new(CancelToken::new());
+        let live = Arc::new(LiveScan::new(selected_disk.path(), &options, cancel_token.clone()));
+        let thread_live = live.clone();
+        let progress = Arc::new(Mutex::new(ScanProgress::default()));
+        let events = App::watch_progress(progress.clone());
+        let thread = thread::spawn(move || {
+            let inspector = Inspector::new(available_size, options, events);
+            return App::populate(inspector, &thread_live);
+        });
+        return Scan { thread, live, progress, cancel_token };
+    }
+
+    /// Reads a directory that isn't the root of a disk, progress is based on the directories read
+    pub fn read_directory_sizes(&self, path: PathBuf, options: ScanOptions) -> Scan {
+        let cancel_token = Arc::new(CancelToken::new());
+        let live = Arc::new(LiveScan::new(path, &options, cancel_token.clone()));
+        let thread_live = live.clone();
+        let progress = Arc::new(Mutex::new(ScanProgress::default()));
+        let events = App::watch_progress(progress.clone());
+        let thread = thread::spawn(move || {
+            let inspector = Inspector::for_directory(options, events);
+            return App::populate(inspector, &thread_live);
+        });
+        return Scan { thread, live, progress, cancel_token };
+    }
+
+    /// Returns a sender for a read's events, the progress is kept up to date from them on another
+    /// thread until the read finishes
+    fn watch_progress(progress: Arc<Mutex<ScanProgress>>) -> Sender<Status> {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for status in receiver {
                 match status {
-                    Status::Reading { percentage } => {
-                        progress_counter.set(percentage)
+                    Status::Started { path } => {
+                        debug!("Disk read started at {:?}", path);
                     }
-                    Status::Done => {
-                        progress_counter.set(100);
-                        debug!("Disk read done");
+                    Status::Reading(scan_progress) => {
+                        *progress.lock().unwrap() = scan_progress;
+                    }
+                    Status::Cancelled => {
+                        debug!("Disk read cancelled");
+                    }
+                    Status::DirEntered { path } => {
+                        trace!("Reading {:?}", path);
+                    }
+                    Status::DirFinished { path, size, allocated_size } => {
+                        trace!("Read {:?}, {} bytes ({} allocated)", path, size, allocated_size);
+                    }
+                    Status::Error(error) => {
+                        warn!("{}", error);
+                    }
+                    Status::Finished(summary) => {
+                        progress.lock().unwrap().percentage = 100;
+                        debug!("Disk read done, {} files and {} directories, {} bytes ({} allocated), {} errors, took {:?}",
+                               summary.files, summary.dirs, summary.size, summary.allocated_size, summary.errors, summary.elapsed);
                     }
-                };
-            });
-
-            match inspector.populate(disk_path) {
-                Ok(root_item) => {
-                    progress_counter.set(100);
-                    debug!("Disk read complete");
-                    return Some(root_item);
-                }
-                Err(e) => {
-                    progress_counter.set(100);
-                    eprintln!("{:?}", e);
-                    error!("Disk read failed: {:?}", e);
-                    return None;
                 }
             }
         });
+        return sender;
     }
-}
 
+    fn populate(mut inspector: Inspector, live: &LiveScan) -> Tree {
+        let tree = inspector.populate_with(live);
+        debug!("Disk read complete, {} errors", tree.errors().len());
+        return tree;
+    }
+}
@@ -0,0 +1,30 @@
+//! Stopping a read from another thread
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Shared between the thread reading the disk and the UI so the UI can ask the read to stop
+#[derive(Debug, Default)]
+pub struct CancelToken {
+    cancelled: AtomicBool
+}
+
+impl CancelToken {
+    /// A token that hasn't been cancelled
+    pub fn new() -> CancelToken {
+        return CancelToken {
+            cancelled: AtomicBool::new(false)
+        };
+    }
+}
+
+impl CancelToken {
+    /// Asks the read to stop, it can't be undone
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Set once `cancel` has been called on any thread
+    pub fn is_cancelled(&self) -> bool {
+        return self.cancelled.load(Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,332 @@
+use clap::{App as ClapApp, AppSettings, Arg, ArgMatches, Error as ClapError, ErrorKind as ClapErrorKind, SubCommand};
+use simplelog::LevelFilter;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use crate::config::Config;
+use disk_inspector::scan_filter::ScanFilter;
+use disk_inspector::scan_options::ScanOptions;
+use disk_inspector::inspector::SizeMode;
+
+
+pub enum Command {
+    /// Pick a disk, or use the directory given, and browse it
+    /// `live` lets the user browse while the scan is running instead of waiting for it to finish,
+    /// `dry_run` stops entries being deleted
+    Tui { path: Option<PathBuf>, options: ScanOptions, live: bool, dry_run: bool },
+    /// Scan a directory and print the largest entries
+    Scan { path: PathBuf, options: ScanOptions, summary: SummaryOptions },
+    /// Print the disks found
+    Disks,
+    /// Scan a directory and write every entry to `output` or stdout
+    Export { path: PathBuf, output: Option<PathBuf>, format: ExportFormat, options: ScanOptions },
+    /// Browse a tree written by export or ncdu
+    Import { file: PathBuf },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    /// The whole tree, can be imported
+    Json,
+    /// One line per entry with its sizes and path
+    Tsv,
+    /// The format written by `ncdu -o`, without the entries that were folded together
+    Ncdu,
+}
+
+pub struct SummaryOptions {
+    /// How many entries to print
+    pub count: usize,
+    /// How many levels below the scanned directory the entries are from
+    pub depth: usize,
+    pub size_mode: SizeMode,
+    /// Print exact byte counts instead of human readable sizes
+    pub exact_bytes: bool,
+}
+
+pub struct Cli {
+    /// Nothing is logged unless this is set
+    pub log_file: Option<PathBuf>,
+    /// `Off` if logging is disabled
+    pub log_level: LevelFilter,
+    pub command: Command,
+}
+
+impl Cli {
+    pub fn parse() -> Cli {
+        let matches = ClapApp::new("Disk Inspector")
+            .version(env!("CARGO_PKG_VERSION"))
+            .about("Disk usage for Windows, macOS and Linux")
+            .setting(AppSettings::VersionlessSubcommands)
+            .arg(Arg::with_name("log-file")
+                .long("log-file")
+                .value_name("FILE")
+                .help("Write a log to FILE, nothing is logged without this")
+                .global(true))
+            .arg(Arg::with_name("log-level")
+                .long("log-level")
+                .value_name("LEVEL")
+                .help("How much to log")
+                .possible_values(&["off", "error", "warn", "info", "debug", "trace"])
+                .case_insensitive(true)
+                .default_value("debug")
+                .global(true))
+            .arg(Arg::with_name("no-log")
+                .long("no-log")
+                .help("Don't write a log")
+                .global(true))
+            .arg(Arg::with_name("config")
+                .long("config")
+                .value_name("FILE")
+                .help("Config file to use instead of config.toml in the user's config directory")
+                .global(true))
+            .arg(Arg::with_name("path")
+                .value_name("PATH")
+                .help("Directory to browse instead of picking a disk"))
+            .arg(live_arg())
+            .arg(dry_run_arg())
+            .args(&scan_args())
+            .subcommand(SubCommand::with_name("tui")
+                .about("Pick a disk or directory and browse it (default)")
+                .arg(Arg::with_name("path")
+                    .value_name("PATH")
+                    .help("Directory to browse instead of picking a disk"))
+                .arg(live_arg())
+                .arg(dry_run_arg())
+                .args(&scan_args()))
+            .subcommand(SubCommand::with_name("scan")
+                .about("Scan a directory and print the largest entries")
+                .arg(Arg::with_name("path")
+                    .value_name("PATH")
+                    .required(true))
+                .arg(Arg::with_name("top")
+                    .short("n")
+                    .long("top")
+                    .value_name("COUNT")
+                    .help("Number of entries to print")
+                    .default_value("10")
+                    .validator(is_number))
+                .arg(Arg::with_name("depth")
+                    .short("d")
+                    .long("depth")
+                    .value_name("DEPTH")
+                    .help("How many levels below PATH to list entries from, 0 only prints PATH")
+                    .default_value("1")
+                    .validator(is_number))
+                .arg(Arg::with_name("bytes")
+                    .short("b")
+                    .long("bytes")
+                    .help("Print sizes in bytes"))
+                .arg(Arg::with_name("disk-usage")
+                    .long("disk-usage")
+                    .help("Use the space allocated on disk instead of the apparent size"))
+                .args(&scan_args()))
+            .subcommand(SubCommand::with_name("disks")
+                .about("List the disks that can be scanned"))
+            .subcommand(SubCommand::with_name("export")
+                .about("Scan a directory and write the size of every entry")
+                .arg(Arg::with_name("path")
+                    .value_name("PATH")
+                    .required(true))
+                .arg(Arg::with_name("output")
+                    .short("o")
+                    .long("output")
+                    .value_name("FILE")
+                    .help("File to write to, stdout is used if not set"))
+                .arg(Arg::with_name("format")
+                    .short("f")
+                    .long("format")
+                    .value_name("FORMAT")
+                    .help("json and ncdu can be opened with the import command, tsv has one line per entry")
+                    .possible_values(&["json", "tsv", "ncdu"])
+                    .default_value("json"))
+                .args(&scan_args()))
+            .subcommand(SubCommand::with_name("import")
+                .about("Browse a scan written by the export command or by ncdu -o")
+                .arg(Arg::with_name("file")
+                    .value_name("FILE")
+                    .required(true)))
+            .get_matches();
+
+        let log_level = if matches.is_present("no-log") || !matches.is_present("log-file") {
+            LevelFilter::Off
+        } else {
+            LevelFilter::from_str(matches.value_of("log-level").unwrap()).unwrap()
+        };
+
+        let config = match Config::load(matches.value_of_os("config").map(Path::new)) {
+            Ok(config) => config,
+            Err(err) => ClapError::with_description(&format!("Unable to read config: {}", err), ClapErrorKind::Io).exit(),
+        };
+
+        let command = match matches.subcommand() {
+            ("scan", Some(sub_matches)) => Command::Scan {
+                path: path_arg(sub_matches).unwrap(),
+                options: scan_options(sub_matches, &config),
+                summary: SummaryOptions {
+                    count: usize::from_str(sub_matches.value_of("top").unwrap()).unwrap(),
+                    depth: usize::from_str(sub_matches.value_of("depth").unwrap()).unwrap(),
+                    size_mode: if sub_matches.is_present("disk-usage") { SizeMode::Allocated } else { SizeMode::Apparent },
+                    exact_bytes: sub_matches.is_present("bytes"),
+                },
+            },
+            ("disks", Some(_)) => Command::Disks,
+            ("export", Some(sub_matches)) => Command::Export {
+                path: path_arg(sub_matches).unwrap(),
+                output: sub_matches.value_of_os("output").map(PathBuf::from),
+                format: match sub_matches.value_of("format").unwrap() {
+                    "tsv" => ExportFormat::Tsv,
+                    "ncdu" => ExportFormat::Ncdu,
+                    _ => ExportFormat::Json,
+                },
+                options: scan_options(sub_matches, &config),
+            },
+            ("import", Some(sub_matches)) => Command::Import {
+                file: PathBuf::from(sub_matches.value_of_os("file").unwrap()),
+            },
+            ("tui", Some(sub_matches)) => Command::Tui {
+                path: path_arg(sub_matches),
+                options: scan_options(sub_matches, &config),
+                live: sub_matches.is_present("live"),
+                dry_run: config.tui.dry_run || sub_matches.is_present("dry-run"),
+            },
+            _ => Command::Tui {
+                path: path_arg(&matches),
+                options: scan_options(&matches, &config),
+                live: matches.is_present("live"),
+                dry_run: config.tui.dry_run || matches.is_present("dry-run"),
+            },
+        };
+
+        return Cli {
+            log_file: matches.value_of_os("log-file").map(PathBuf::from),
+            log_level,
+            command,
+        };
+    }
+}
+
+fn scan_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    return vec![
+        Arg::with_name("threads")
+            .long("threads")
+            .value_name("COUNT")
+            .help("Number of threads used to read directories, defaults to the number of CPUs")
+            .validator(is_number),
+        Arg::with_name("cross-filesystems")
+            .long("cross-filesystems")
+            .help("Read directories on other filesystems mounted below the scanned directory"),
+        Arg::with_name("exclude")
+            .long("exclude")
+            .value_name("PATTERN")
+            .help("Don't read or count entries whose path or name matches this glob, can be given more than once")
+            .multiple(true)
+            .number_of_values(1),
+        Arg::with_name("ignore-files")
+            .long("ignore-files")
+            .help("Read .gitignore and .ignore files and mark what they match as ignored, press i to filter by it"),
+        Arg::with_name("max-depth")
+            .long("max-depth")
+            .value_name("DEPTH")
+            .help("Fold everything more than DEPTH levels below the scanned directory into one entry per directory to save memory")
+            .validator(is_number),
+        Arg::with_name("min-size")
+            .long("min-size")
+            .value_name("SIZE")
+            .help("Fold entries smaller than SIZE (e.g. 4096, 100k, 10M) into one entry per directory to save memory")
+            .validator(is_size),
+        Arg::with_name("follow-symlinks")
+            .long("follow-symlinks")
+            .help("Count what symlinks point to instead of the links, anything reached more than once is only counted once"),
+        Arg::with_name("include")
+            .long("include")
+            .value_name("PATTERN")
+            .help("Only count files whose path or name matches this glob, can be given more than once")
+            .multiple(true)
+            .number_of_values(1),
+    ];
+}
+
+fn live_arg<'a, 'b>() -> Arg<'a, 'b> {
+    return Arg::with_name("live")
+        .long("live")
+        .help("Browse while the scan is running, directories still being read are marked with …");
+}
+
+fn dry_run_arg<'a, 'b>() -> Arg<'a, 'b> {
+    return Arg::with_name("dry-run")
+        .long("dry-run")
+        .help("Only show what pressing d would delete, nothing is deleted");
+}
+
+fn is_number(value: String) -> Result<(), String> {
+    return usize::from_str(&value).map(|_| ()).map_err(|err| err.to_string());
+}
+
+fn is_size(value: String) -> Result<(), String> {
+    return parse_size(&value).map(|_| ());
+}
+
+/// Parses a number of bytes with an optional k, M, G or T suffix, which are powers of 1024
+fn parse_size(value: &str) -> Result<u64, String> {
+    let (number, multiplier) = match value.chars().last() {
+        Some('k') | Some('K') => (&value[..value.len() - 1], 1 << 10),
+        Some('m') | Some('M') => (&value[..value.len() - 1], 1 << 20),
+        Some('g') | Some('G') => (&value[..value.len() - 1], 1 << 30),
+        Some('t') | Some('T') => (&value[..value.len() - 1], 1 << 40),
+        _ => (value, 1),
+    };
+    let number = u64::from_str(number).map_err(|err| format!("{}: {}", value, err))?;
+    return number.checked_mul(multiplier).ok_or_else(|| format!("{}: size too large", value));
+}
+
+/// The include and exclude patterns are the ones from the config followed by the ones given
+fn scan_options(matches: &ArgMatches, config: &Config) -> ScanOptions {
+    let mut options = ScanOptions::new();
+    if let Some(threads) = matches.value_of("threads") {
+        options.threads = usize::from_str(threads).unwrap().max(1);
+    }
+    options.one_file_system = !matches.is_present("cross-filesystems");
+    options.use_ignore_files = config.scan.ignore_files || matches.is_present("ignore-files");
+    options.follow_symlinks = config.scan.follow_symlinks || matches.is_present("follow-symlinks");
+    options.max_depth = matches.value_of("max-depth").map(|depth| usize::from_str(depth).unwrap());
+    if let Some(min_size) = matches.value_of("min-size") {
+        options.min_size = parse_size(min_size).unwrap();
+    }
+    let include = patterns(&config.scan.include, matches, "include");
+    let exclude = patterns(&config.scan.exclude, matches, "exclude");
+    options.filter = match ScanFilter::new(&include, &exclude) {
+        Ok(filter) => filter,
+        Err(err) => ClapError::with_description(&format!("Invalid pattern: {}", err), ClapErrorKind::InvalidValue).exit(),
+    };
+    return options;
+}
+
+fn patterns(configured: &[String], matches: &ArgMatches, name: &str) -> Vec<String> {
+    let mut patterns = configured.to_vec();
+    if let Some(values) = matches.values_of(name) {
+        patterns.extend(values.map(String::from));
+    }
+    return patterns;
+}
+
+fn path_arg(matches: &ArgMatches) -> Option<PathBuf> {
+    return matches.value_of_os("path").map(PathBuf::from);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_have_optional_suffixes() {
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size("100k"), Ok(100 << 10));
+        assert_eq!(parse_size("10M"), Ok(10 << 20));
+        assert!(parse_size("10x").is_err());
+    }
+
+    #[test]
+    fn sizes_that_dont_fit_are_rejected() {
+        assert_eq!(parse_size("99999999999T"), Err("99999999999T: size too large".to_string()));
+    }
+}
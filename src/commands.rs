@@ -0,0 +1,92 @@
+use std::fs::File;
+use std::io::{stdout, BufWriter, Error, ErrorKind, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::mpsc;
+use disk_inspector::analysis::largest_at_depth;
+use crate::app::App;
+use crate::cli::{ExportFormat, SummaryOptions};
+use disk_inspector::export::{write_json, write_tsv};
+use disk_inspector::format::human_readable_bytes;
+use disk_inspector::inspector::{Disk, Inspector};
+use disk_inspector::ncdu::write_ncdu;
+use disk_inspector::scan_options::ScanOptions;
+use disk_inspector::tree::{Tree, ROOT};
+
+/// Prints the disks found, one per line
+pub fn print_disks(app: &App) -> Result<(), Error> {
+    let disks = app.setup()?;
+    for disk in disks {
+        println!("{:<20} {:<30} {:>10} free of {:>10}",
+                 disk.name,
+                 disk.path().display(),
+                 human_readable_bytes(disk.available_space as f64),
+                 human_readable_bytes(disk.total_space as f64));
+    }
+    return Ok(());
+}
+
+/// Scans `path` and prints its size followed by the largest entries at the requested depth,
+/// this only writes plain text so can be used without a terminal
+pub fn print_summary(path: PathBuf, options: ScanOptions, summary: SummaryOptions) -> Result<(), Error> {
+    let tree = scan(path, options)?;
+    let format_size = |size: u64| {
+        if summary.exact_bytes {
+            return size.to_string();
+        }
+        return human_readable_bytes(size as f64);
+    };
+
+    println!("{:>14}  {}", format_size(tree.size_for(ROOT, summary.size_mode)), tree.path(ROOT).display());
+    if summary.depth > 0 {
+        for id in largest_at_depth(&tree, summary.depth, summary.count, summary.size_mode) {
+            println!("{:>14}  {}", format_size(tree.size_for(id, summary.size_mode)), tree.path(id).display());
+        }
+    }
+    return Ok(());
+}
+
+/// Scans `path` and writes it to `output`, or stdout if not set
+pub fn export(app: &App, path: PathBuf, output: Option<PathBuf>, format: ExportFormat, options: ScanOptions) -> Result<(), Error> {
+    let mut disk = app.disk_for_path(path)?;
+    disk.tree = Arc::new(scan(disk.path(), options)?);
+    match output {
+        Some(output) => {
+            let mut writer = BufWriter::new(File::create(output)?);
+            write_export(&disk, format, &mut writer)?;
+            writer.flush()?;
+        }
+        None => {
+            let stdout = stdout();
+            let mut writer = BufWriter::new(stdout.lock());
+            write_export(&disk, format, &mut writer)?;
+            writer.flush()?;
+        }
+    }
+    return Ok(());
+}
+
+fn write_export(disk: &Disk, format: ExportFormat, writer: &mut impl Write) -> Result<(), Error> {
+    match format {
+        ExportFormat::Json => return write_json(disk, writer),
+        ExportFormat::Tsv => return write_tsv(&disk.tree, writer),
+        ExportFormat::Ncdu => return write_ncdu(disk, writer),
+    }
+}
+
+fn scan(path: PathBuf, options: ScanOptions) -> Result<Tree, Error> {
+    let path = path.canonicalize()?;
+    if !path.is_dir() {
+        return Err(Error::new(ErrorKind::InvalidInput, format!("{} is not a directory", path.display())));
+    }
+    debug!("Scanning {:?}", path);
+    //nothing is listening for the events so they are dropped
+    let (events, _) = mpsc::channel();
+    let mut inspector = Inspector::for_directory(options, events);
+    let tree = inspector.populate(path);
+    //written to stderr like du does so the output can still be used
+    for error in tree.errors() {
+        eprintln!("{}", error);
+    }
+    return Ok(tree);
+}
@@ -0,0 +1,64 @@
+use serde::Deserialize;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+const CONFIG_DIR: &str = "disk-inspector";
+const CONFIG_FILE: &str = "config.toml";
+
+/// Settings read from `config.toml` in the user's config directory, or the file given with `--config`
+///
+/// ```toml
+/// [scan]
+/// exclude = [".git", "node_modules", "/proc"]
+/// include = []
+///
+/// [tui]
+/// dry_run = true
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub scan: ScanConfig,
+    pub tui: TuiConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ScanConfig {
+    /// Patterns added to the ones given with `--include`
+    pub include: Vec<String>,
+    /// Patterns added to the ones given with `--exclude`
+    pub exclude: Vec<String>,
+    /// Always read ignore files, as if `--ignore-files` was given
+    pub ignore_files: bool,
+    /// Always follow symlinks, as if `--follow-symlinks` was given
+    pub follow_symlinks: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct TuiConfig {
+    /// Never delete anything, as if `--dry-run` was given
+    pub dry_run: bool,
+}
+
+impl Config {
+    /// Reads `file` if given, otherwise the default config file if there is one
+    pub fn load(file: Option<&Path>) -> Result<Config, Error> {
+        let path = match file {
+            Some(file) => file.to_path_buf(),
+            None => match Config::default_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Config::default()),
+            }
+        };
+        let text = fs::read_to_string(&path)?;
+        return toml::from_str(&text)
+            .map_err(|err| Error::new(ErrorKind::InvalidData, format!("{}: {}", path.display(), err)));
+    }
+
+    fn default_path() -> Option<PathBuf> {
+        return dirs::config_dir().map(|dir| dir.join(CONFIG_DIR).join(CONFIG_FILE));
+    }
+}
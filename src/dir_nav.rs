@@ -0,0 +1,630 @@
+use disk_inspector::file_system::{FileSystem, RealFileSystem};
+use disk_inspector::format::human_readable_bytes;
+use disk_inspector::inspector::{Disk, IgnoreFilter, SizeMode, SortMode};
+use disk_inspector::tree::{NodeId, Tree, ROOT};
+use crate::app::Scan;
+use crate::terminal_helper::TerminalHelper;
+use crate::view::{draw_dir_items, error_count_text, show_error_list, DirAction, DirListOptions};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often the list is redrawn while the tree is still being read
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct DirNav {
+    disk: Box<Disk>,
+    size_mode: SizeMode,
+    ignore_filter: IgnoreFilter,
+    sort_mode: SortMode,
+    /// Set while the tree is still being read
+    scan: Option<Scan>,
+    /// What entries are deleted from
+    file_system: Arc<dyn FileSystem>,
+    /// Deleting only says what would have been deleted
+    dry_run: bool,
+    /// Set for trees read from a file, which may be out of date or from another machine so nothing is deleted
+    imported: bool,
+}
+
+impl DirNav {
+    pub fn new(disk: Disk) -> DirNav {
+        return DirNav::with_file_system(disk, Arc::new(RealFileSystem));
+    }
+
+    /// Entries are deleted from `file_system` instead of the disk
+    pub fn with_file_system(disk: Disk, file_system: Arc<dyn FileSystem>) -> DirNav {
+        return DirNav {
+            disk: Box::new(disk),
+            size_mode: SizeMode::Apparent,
+            ignore_filter: IgnoreFilter::All,
+            sort_mode: SortMode::default(),
+            scan: None,
+            file_system,
+            dry_run: false,
+            imported: false,
+        };
+    }
+
+    /// Browse the tree while it's being read, directories are filled in as they are read
+    pub fn new_live(mut disk: Disk, scan: Scan) -> DirNav {
+        if let Some(tree) = scan.live.root_snapshot() {
+            disk.tree = Arc::new(tree);
+        }
+        return DirNav {
+            disk: Box::new(disk),
+            size_mode: SizeMode::Apparent,
+            ignore_filter: IgnoreFilter::All,
+            sort_mode: SortMode::default(),
+            scan: Some(scan),
+            file_system: Arc::new(RealFileSystem),
+            dry_run: false,
+            imported: false,
+        };
+    }
+}
+
+impl DirNav {
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    pub fn set_imported(&mut self, imported: bool) {
+        self.imported = imported;
+    }
+
+    /// Lets the user browse until Esc is pressed
+    pub fn navigate_directory(&mut self, terminal_helper: &TerminalHelper) {
+        //The directories from the root to the current one, while the tree is being read
+        //each one is the root of its own snapshot
+        let mut dirs = vec![(self.disk.tree.clone(), ROOT)];
+        let mut cursor_idx = 0;
+
+        loop {
+            terminal_helper.clear_screen();
+
+            self.refresh(&mut dirs);
+
+            let (tree, current_dir) = dirs.last().unwrap().clone();
+
+            let mut title = format!("{} ({}, {})", tree.path(current_dir).to_string_lossy(), self.size_mode.name(), self.sort_mode.name());
+            if self.ignore_filter != IgnoreFilter::All {
+                title.push_str(&format!(" {} only", self.ignore_filter.name()));
+            }
+            let error_count = match &self.scan {
+                Some(scan) => scan.live.error_count(),
+                None => self.disk.tree.errors().len(),
+            };
+            if error_count > 0 {
+                title.push_str(&format!(" {}", error_count_text(error_count)));
+            }
+            let refresh_interval = match &self.scan {
+                Some(scan) => {
+                    title.push_str(&format!(" reading {}%", scan.progress.lock().unwrap().percentage));
+                    Some(REFRESH_INTERVAL)
+                }
+                None => None,
+            };
+            let options = DirListOptions {
+                show_go_up: dirs.len() > 1,
+                size_mode: self.size_mode,
+                ignore_filter: self.ignore_filter,
+                sort_mode: self.sort_mode,
+                refresh_interval,
+            };
+
+            debug!("Navigating {} on {} with {} children", title, self.disk.name, tree.children(current_dir).len());
+
+            match draw_dir_items(terminal_helper, title, &tree, current_dir, &options, cursor_idx) {
+                DirAction::Exit => {
+                    return;
+                }
+                DirAction::ToggleSizeMode(idx) => {
+                    self.size_mode = self.size_mode.toggle();
+                    debug!("Size mode changed to {:?}", self.size_mode);
+                    cursor_idx = idx;
+                }
+                DirAction::ToggleIgnoreFilter(idx) => {
+                    self.ignore_filter = self.ignore_filter.next();
+                    debug!("Ignore filter changed to {:?}", self.ignore_filter);
+                    cursor_idx = idx;
+                }
+                DirAction::ToggleSortMode(idx) => {
+                    self.sort_mode = self.sort_mode.next();
+                    debug!("Sort mode changed to {:?}", self.sort_mode);
+                    cursor_idx = idx;
+                }
+                DirAction::Delete(child, idx) => {
+                    cursor_idx = idx;
+                    //the tree can only be changed once nothing else refers to it
+                    drop(tree);
+                    self.delete(terminal_helper, &mut dirs, child);
+                }
+                DirAction::ShowErrors(idx) => {
+                    cursor_idx = idx;
+                    let errors = match &self.scan {
+                        Some(scan) => scan.live.errors(),
+                        None => self.disk.tree.errors().to_vec(),
+                    };
+                    //the tree is only complete once the read has finished so entries can't be found before then
+                    if let Some(error_idx) = show_error_list(terminal_helper, &errors) {
+                        if self.scan.is_none() {
+                            if let Some(idx) = self.open_path(&mut dirs, &errors[error_idx].path) {
+                                cursor_idx = idx;
+                            }
+                        }
+                    }
+                }
+                DirAction::Refresh(idx) => {
+                    cursor_idx = idx;
+                }
+                DirAction::Up => {
+                    dirs.pop();
+                    cursor_idx = 0;
+                }
+                DirAction::Open(child) => {
+                    debug!("Opening {}", tree.name(child));
+                    dirs.push((tree.clone(), child));
+                    cursor_idx = 0;
+                }
+            }
+        }
+    }
+
+    /// Asks before deleting the entry from the disk, then takes it out of the tree so the sizes of the
+    /// directories above it go down without reading them again. Failures are shown, a directory that failed may have
+    /// been partly deleted so it's marked as unread. Symlinks, anything read through a symlink and anything containing
+    /// mount points, excluded entries or directories that weren't read aren't deleted as what would be removed from
+    /// the disk isn't what the tree shows.
+    fn delete(&mut self, terminal_helper: &TerminalHelper, dirs: &mut Vec<(Arc<Tree>, NodeId)>, id: NodeId) {
+        let (tree, _) = dirs.last().unwrap();
+        //snapshots are replaced as the read goes on so changes to them would be lost
+        if self.scan.is_some() || !Arc::ptr_eq(tree, &self.disk.tree) {
+            terminal_helper.show_message("Entries can't be deleted until the read has finished");
+            return;
+        }
+        if self.imported {
+            terminal_helper.show_message("Entries can't be deleted from an imported tree, it may not match the disk");
+            return;
+        }
+        let item = tree.item(id);
+        if item.aggregate_count > 0 {
+            terminal_helper.show_message(format!("{} are folded together and can't be deleted", item.aggregate_name()));
+            return;
+        }
+        let path = tree.path(id);
+        let refusal = if item.is_symlink {
+            Some((id, "is a symlink"))
+        } else if DirNav::is_under_followed_link(tree, id) {
+            Some((id, "was read through a symlink"))
+        } else {
+            DirNav::find_unread(tree, id)
+        };
+        match refusal {
+            Some((refused, reason)) if refused == id => {
+                terminal_helper.show_message(format!("{} {}, it can't be deleted", path.display(), reason));
+                return;
+            }
+            Some((refused, reason)) => {
+                terminal_helper.show_message(format!("{} {}, {} can't be deleted", tree.path(refused).display(), reason, path.display()));
+                return;
+            }
+            None => {}
+        }
+        let description = format!("{} ({})", path.display(), human_readable_bytes(item.size_for(self.size_mode) as f64));
+        if !terminal_helper.confirm(format!("Delete {}?", description)) {
+            return;
+        }
+        if self.dry_run {
+            info!("Dry run, not deleting {}", path.display());
+            terminal_helper.show_message(format!("Dry run, {} was not deleted", description));
+            return;
+        }
+        match self.file_system.remove(&path) {
+            Ok(()) => {
+                info!("Deleted {}", path.display());
+                self.change_tree(dirs, |tree| tree.remove(id));
+            }
+            Err(err) => {
+                error!("Unable to delete {}: {}", path.display(), err);
+                if item.is_dir {
+                    self.change_tree(dirs, |tree| tree.mark_unread(id));
+                }
+                terminal_helper.show_message(format!("Unable to delete {}: {}", path.display(), err));
+            }
+        }
+    }
+
+    /// Changes the tree, which has to be the complete one, with the same directories left open
+    fn change_tree(&mut self, dirs: &mut Vec<(Arc<Tree>, NodeId)>, change: impl FnOnce(&mut Tree)) {
+        let open_dirs: Vec<NodeId> = dirs.drain(..).map(|(_, dir)| dir).collect();
+        change(Arc::make_mut(&mut self.disk.tree));
+        dirs.extend(open_dirs.into_iter().map(|dir| (self.disk.tree.clone(), dir)));
+    }
+
+    /// The first entry, depth first, in or under the node that wasn't completely read and why, deleting it would
+    /// remove what the tree doesn't show. Directories above one that wasn't read are pending too so they're skipped.
+    fn find_unread(tree: &Tree, id: NodeId) -> Option<(NodeId, &'static str)> {
+        let mut nodes = vec![id];
+        while let Some(node) = nodes.pop() {
+            let item = tree.item(node);
+            let reason = if item.is_mount_point {
+                Some("is on another filesystem and wasn't read")
+            } else if item.is_excluded {
+                Some("was excluded from the read")
+            } else if item.read_error {
+                Some("couldn't be read")
+            } else if item.is_pending && !tree.children(node).iter().any(|child| tree.item(*child).is_pending) {
+                Some("wasn't read completely")
+            } else {
+                None
+            };
+            if let Some(reason) = reason {
+                return Some((node, reason));
+            }
+            nodes.extend(tree.children(node).iter().rev());
+        }
+        return None;
+    }
+
+    /// True if one of the node's parents is a symlink that was followed, the node's path then
+    /// leads through the link to somewhere that may not be under the root
+    fn is_under_followed_link(tree: &Tree, id: NodeId) -> bool {
+        let mut ancestor = tree.parent(id);
+        while let Some(ancestor_id) = ancestor {
+            let item = tree.item(ancestor_id);
+            if item.is_symlink && item.is_dir {
+                return true;
+            }
+            ancestor = tree.parent(ancestor_id);
+        }
+        return false;
+    }
+
+    /// Opens the directory containing `path` and returns the index of `path` in the list,
+    /// or None if it isn't in the tree
+    fn open_path(&self, dirs: &mut Vec<(Arc<Tree>, NodeId)>, path: &Path) -> Option<usize> {
+        let tree = self.disk.tree.clone();
+        let target = tree.find_path(path)?;
+        let parent = tree.parent(target)?;
+        let mut ancestors = vec![parent];
+        while let Some(ancestor) = tree.parent(*ancestors.last().unwrap()) {
+            ancestors.push(ancestor);
+        }
+        dirs.clear();
+        dirs.extend(ancestors.into_iter().rev().map(|ancestor| (tree.clone(), ancestor)));
+
+        let go_up_offset = if dirs.len() > 1 { 1 } else { 0 };
+        let mut children: Vec<NodeId> = tree.children(parent)
+            .iter()
+            .filter(|child| tree.item(**child).is_shown(self.ignore_filter))
+            .cloned()
+            .collect();
+        self.sort_mode.sort(&tree, &mut children, self.size_mode, self.ignore_filter);
+        let idx = children.iter().position(|child| *child == target).unwrap_or(0);
+        return Some(idx + go_up_offset);
+    }
+
+    /// While the scan is running this replaces the current directory with what has been read so far,
+    /// once it has finished the complete tree is used instead with the same directories open
+    fn refresh(&mut self, dirs: &mut Vec<(Arc<Tree>, NodeId)>) {
+        let scan = match &self.scan {
+            Some(scan) => scan,
+            None => return,
+        };
+
+        if !scan.thread.is_finished() {
+            let (tree, current_dir) = dirs.last_mut().unwrap();
+            if let Some(snapshot) = scan.live.snapshot(tree, *current_dir) {
+                *tree = Arc::new(snapshot);
+                *current_dir = ROOT;
+            }
+            return;
+        }
+
+        let scan = self.scan.take().unwrap();
+        match scan.thread.join() {
+            Ok(tree) => {
+                debug!("Read finished while browsing");
+                let tree = Arc::new(tree);
+                self.disk.tree = tree.clone();
+                let names: Vec<String> = dirs.iter()
+                    .skip(1)
+                    .map(|(tree, dir)| tree.path(*dir).file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default())
+                    .collect();
+                dirs.clear();
+                dirs.push((tree.clone(), ROOT));
+                for name in names {
+                    let (_, parent) = dirs.last().unwrap();
+                    match tree.find_child(*parent, &name).filter(|child| tree.item(*child).is_dir) {
+                        Some(child) => dirs.push((tree.clone(), child)),
+                        None => break,
+                    }
+                }
+            }
+            Err(_) => {
+                error!("Read failed while browsing, showing what was read");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_terminal::{assert_screen, FakeTerminal};
+    use crossterm::KeyEvent;
+    use disk_inspector::cancel_token::CancelToken;
+    use disk_inspector::file_system::{DirEntry, FileInfo, MemoryFileSystem};
+    use disk_inspector::inspector::{Inspector, LiveScan};
+    use disk_inspector::scan_filter::ScanFilter;
+    use disk_inspector::scan_options::ScanOptions;
+    use std::io::{Error, ErrorKind, Result};
+    use std::path::PathBuf;
+    use std::sync::mpsc;
+
+    /// Deletes the files directly in a directory and then fails, as if something further down couldn't be deleted
+    struct PartlyDeleting(Arc<MemoryFileSystem>);
+
+    impl FileSystem for PartlyDeleting {
+        fn read_dir(&self, path: &Path) -> Result<Vec<Result<DirEntry>>> {
+            return self.0.read_dir(path);
+        }
+
+        fn metadata(&self, path: &Path) -> Result<FileInfo> {
+            return self.0.metadata(path);
+        }
+
+        fn read_link(&self, path: &Path) -> Result<PathBuf> {
+            return self.0.read_link(path);
+        }
+
+        fn read_to_string(&self, path: &Path) -> Result<String> {
+            return self.0.read_to_string(path);
+        }
+
+        fn remove(&self, path: &Path) -> Result<()> {
+            for dir_entry in self.0.read_dir(path)? {
+                let dir_entry = dir_entry?;
+                if !dir_entry.info?.is_dir {
+                    self.0.remove(&path.join(&dir_entry.name))?;
+                }
+            }
+            return Err(Error::new(ErrorKind::PermissionDenied, "Permission denied"));
+        }
+    }
+
+    fn files() -> MemoryFileSystem {
+        let mut fs = MemoryFileSystem::new();
+        fs.add_file("/data/photos/cat.jpg", 3000);
+        fs.add_file("/data/photos/dog.jpg", 5000);
+        fs.add_file("/data/photos/2019/beach.jpg", 10_000);
+        fs.add_file("/data/notes.txt", 100);
+        fs.add_dir("/data/empty");
+        fs.deny_read_dir("/data/private");
+        return fs;
+    }
+
+    /// Has a mount point, an excluded directory, a symlink to a file and a followed symlink to a directory outside /data
+    fn links() -> MemoryFileSystem {
+        let mut fs = files();
+        fs.add_mount_point("/data/backup");
+        fs.add_file("/data/backup/disk.img", 50_000);
+        fs.add_file("/data/cache/page.html", 2000);
+        fs.add_symlink("/data/latest", "photos/cat.jpg");
+        fs.add_file("/other/pictures/pic.jpg", 20_000);
+        fs.add_symlink("/data/album", "/other/pictures");
+        return fs;
+    }
+
+    fn disk(fs: Arc<MemoryFileSystem>) -> Disk {
+        return disk_with(fs, &ScanOptions::new());
+    }
+
+    fn disk_with(fs: Arc<MemoryFileSystem>, options: &ScanOptions) -> Disk {
+        let (events, _receiver) = mpsc::channel();
+        let scan = LiveScan::with_file_system(PathBuf::from("/data"), options, Arc::new(CancelToken::new()), fs);
+        let tree = Inspector::for_directory(options.clone(), events).populate_with(&scan);
+        return Disk { name: "/data".to_string(), available_space: 0, total_space: 0, tree: Arc::new(tree) };
+    }
+
+    fn browse(keys: Vec<KeyEvent>) -> Vec<String> {
+        let (screens, _) = browse_files(keys, false);
+        return screens;
+    }
+
+    /// Also returns the files browsed so a test can check what was deleted
+    fn browse_files(keys: Vec<KeyEvent>, dry_run: bool) -> (Vec<String>, Arc<MemoryFileSystem>) {
+        let fs = Arc::new(files());
+        let terminal = FakeTerminal::new(100, 12, keys);
+        let terminal_helper = TerminalHelper::with_backend(Box::new(terminal.clone()));
+
+        let mut dir_nav = DirNav::with_file_system(disk(fs.clone()), fs.clone());
+        dir_nav.set_dry_run(dry_run);
+        dir_nav.navigate_directory(&terminal_helper);
+
+        return (terminal.screens(), fs);
+    }
+
+    /// Browses the tree of `fs` and deletes from `file_system`
+    fn browse_deleting(fs: Arc<MemoryFileSystem>, file_system: Arc<dyn FileSystem>, keys: Vec<KeyEvent>) -> Vec<String> {
+        let terminal = FakeTerminal::new(100, 12, keys);
+        let terminal_helper = TerminalHelper::with_backend(Box::new(terminal.clone()));
+
+        let mut dir_nav = DirNav::with_file_system(disk(fs), file_system);
+        dir_nav.navigate_directory(&terminal_helper);
+
+        return terminal.screens();
+    }
+
+    /// Browses `links` scanned following symlinks and without the cache directory
+    fn browse_links(keys: Vec<KeyEvent>) -> (Vec<String>, Arc<MemoryFileSystem>) {
+        let fs = Arc::new(links());
+        let mut options = ScanOptions::new();
+        options.follow_symlinks = true;
+        options.filter = ScanFilter::new(&[], &["cache".to_string()]).unwrap();
+        let terminal = FakeTerminal::new(100, 12, keys);
+        let terminal_helper = TerminalHelper::with_backend(Box::new(terminal.clone()));
+
+        let mut dir_nav = DirNav::with_file_system(disk_with(fs.clone(), &options), fs.clone());
+        dir_nav.navigate_directory(&terminal_helper);
+
+        return (terminal.screens(), fs);
+    }
+
+    #[test]
+    fn opens_directories_and_goes_back_up() {
+        let screens = browse(vec![KeyEvent::Char('\n'), KeyEvent::Down, KeyEvent::Char('\n'), KeyEvent::Char('\n'), KeyEvent::Esc]);
+
+        assert_screen("browser_root", &screens[0]);
+        assert_screen("browser_photos", &screens[1]);
+        assert_screen("browser_2019", &screens[3]);
+        assert_eq!(screens[4], screens[1]);
+    }
+
+    #[test]
+    fn files_and_unreadable_directories_are_not_opened() {
+        let screens = browse(vec![KeyEvent::Down, KeyEvent::Char('\n'), KeyEvent::Down, KeyEvent::Down, KeyEvent::Char('\n'), KeyEvent::Esc]);
+
+        //Enter on notes.txt does nothing, private has no entries as it couldn't be read
+        assert_eq!(screens[2], screens[1]);
+        assert_screen("browser_private", &screens[5]);
+    }
+
+    #[test]
+    fn size_mode_is_shown_in_the_title() {
+        let screens = browse(vec![KeyEvent::Char('a'), KeyEvent::Esc]);
+
+        assert_screen("browser_allocated", &screens[1]);
+    }
+
+    #[test]
+    fn errors_can_be_listed_and_opened() {
+        let screens = browse(vec![KeyEvent::Char('e'), KeyEvent::Char('\n'), KeyEvent::Esc]);
+
+        assert_screen("browser_errors", &screens[1]);
+        //the cursor is on the entry that couldn't be read
+        assert_eq!(screens[2], screens[0].replace("▶ photos", "  photos").replace("  private", "▶ private"));
+    }
+
+    #[test]
+    fn sorting_keeps_the_cursor_and_go_up_entry_in_place() {
+        let screens = browse(vec![KeyEvent::Char('\n'), KeyEvent::Down, KeyEvent::Down, KeyEvent::Char('s'), KeyEvent::Char('s'), KeyEvent::Esc]);
+
+        assert_screen("browser_sorted_smallest_first", &screens[4]);
+        assert_screen("browser_sorted_by_name", &screens[5]);
+    }
+
+    #[test]
+    fn deleting_asks_first_and_takes_the_size_off_the_parents() {
+        let keys = vec![KeyEvent::Char('\n'), KeyEvent::Down, KeyEvent::Char('d'), KeyEvent::Char('y'), KeyEvent::Up, KeyEvent::Char('\n'), KeyEvent::Esc];
+        let (screens, fs) = browse_files(keys, false);
+
+        assert_screen("delete_confirm", &screens[3]);
+        assert_screen("delete_photos", &screens[4]);
+        assert_screen("delete_root", &screens[6]);
+        assert!(fs.metadata(Path::new("/data/photos/2019/beach.jpg")).is_err());
+        assert!(fs.metadata(Path::new("/data/photos/cat.jpg")).is_ok());
+    }
+
+    #[test]
+    fn deleting_can_be_cancelled_or_only_shown() {
+        let keys = vec![KeyEvent::Down, KeyEvent::Char('d'), KeyEvent::Char('n'), KeyEvent::Char('d'), KeyEvent::Char('y'), KeyEvent::Char('\n'), KeyEvent::Esc];
+        let (screens, fs) = browse_files(keys, true);
+
+        assert_eq!(screens[3], screens[1]);
+        assert_screen("delete_dry_run", &screens[5]);
+        assert_eq!(screens[6], screens[1]);
+        assert!(fs.metadata(Path::new("/data/notes.txt")).is_ok());
+    }
+
+    #[test]
+    fn failed_deletes_are_shown_and_the_directory_is_marked_unread() {
+        let fs = Arc::new(files());
+        let keys = vec![KeyEvent::Char('d'), KeyEvent::Char('y'), KeyEvent::Char('\n'), KeyEvent::Esc];
+        let screens = browse_deleting(fs.clone(), Arc::new(PartlyDeleting(fs.clone())), keys);
+
+        assert_screen("delete_failed", &screens[2]);
+        assert_screen("delete_failed_unread", &screens[3]);
+        assert!(fs.metadata(Path::new("/data/photos/cat.jpg")).is_err());
+        assert!(fs.metadata(Path::new("/data/photos/2019/beach.jpg")).is_ok());
+    }
+
+    #[test]
+    fn unreadable_directories_are_not_deleted() {
+        let keys = vec![KeyEvent::Down, KeyEvent::Down, KeyEvent::Down, KeyEvent::Char('d'), KeyEvent::Char('\n'), KeyEvent::Esc];
+        let (screens, fs) = browse_files(keys, false);
+
+        assert_screen("delete_unreadable", &screens[4]);
+        assert_eq!(screens[5], screens[3]);
+        assert!(fs.metadata(Path::new("/data/private")).is_ok());
+    }
+
+    #[test]
+    fn directories_containing_a_mount_point_are_not_deleted() {
+        let mut files = files();
+        files.add_mount_point("/data/photos/2019/usb");
+        files.add_file("/data/photos/2019/usb/video.mp4", 80_000);
+        let fs = Arc::new(files);
+        let screens = browse_deleting(fs.clone(), fs.clone(), vec![KeyEvent::Char('d'), KeyEvent::Char('\n'), KeyEvent::Esc]);
+
+        assert_screen("delete_containing_mount_point", &screens[1]);
+        assert_eq!(screens[2], screens[0]);
+        assert!(fs.metadata(Path::new("/data/photos/cat.jpg")).is_ok());
+    }
+
+    #[test]
+    fn imported_trees_are_not_deleted_from() {
+        let fs = Arc::new(files());
+        let terminal = FakeTerminal::new(100, 12, vec![KeyEvent::Char('d'), KeyEvent::Esc, KeyEvent::Esc]);
+        let terminal_helper = TerminalHelper::with_backend(Box::new(terminal.clone()));
+        let mut dir_nav = DirNav::with_file_system(disk(fs.clone()), fs.clone());
+        dir_nav.set_imported(true);
+
+        dir_nav.navigate_directory(&terminal_helper);
+
+        let screens = terminal.screens();
+        assert_screen("delete_imported", &screens[1]);
+        assert_eq!(screens[2], screens[0]);
+        assert!(fs.metadata(Path::new("/data/photos")).is_ok());
+    }
+
+    #[test]
+    fn mount_points_are_not_deleted() {
+        let keys = vec![KeyEvent::Down, KeyEvent::Down, KeyEvent::Down, KeyEvent::Down, KeyEvent::Char('d'), KeyEvent::Esc, KeyEvent::Esc];
+        let (screens, fs) = browse_links(keys);
+
+        assert_screen("delete_mount_point", &screens[5]);
+        assert_eq!(screens[6], screens[4]);
+        assert!(fs.metadata(Path::new("/data/backup/disk.img")).is_ok());
+    }
+
+    #[test]
+    fn excluded_entries_are_not_deleted() {
+        let keys = vec![KeyEvent::Down, KeyEvent::Down, KeyEvent::Down, KeyEvent::Down, KeyEvent::Down, KeyEvent::Char('d'), KeyEvent::Esc, KeyEvent::Esc];
+        let (screens, fs) = browse_links(keys);
+
+        assert_screen("delete_excluded", &screens[6]);
+        assert_eq!(screens[7], screens[5]);
+        assert!(fs.metadata(Path::new("/data/cache/page.html")).is_ok());
+    }
+
+    #[test]
+    fn symlinks_are_not_deleted() {
+        let (screens, fs) = browse_links(vec![KeyEvent::Char('d'), KeyEvent::Esc, KeyEvent::Esc]);
+
+        assert_screen("delete_symlink", &screens[1]);
+        assert_eq!(screens[2], screens[0]);
+        assert!(fs.metadata(Path::new("/data/album")).is_ok());
+    }
+
+    #[test]
+    fn entries_read_through_a_symlink_are_not_deleted() {
+        let keys = vec![KeyEvent::Char('\n'), KeyEvent::Down, KeyEvent::Char('d'), KeyEvent::Esc, KeyEvent::Esc];
+        let (screens, fs) = browse_links(keys);
+
+        assert_screen("delete_through_symlink", &screens[3]);
+        assert_eq!(screens[4], screens[2]);
+        assert!(fs.metadata(Path::new("/other/pictures/pic.jpg")).is_ok());
+    }
+}
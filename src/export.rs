@@ -0,0 +1,26 @@
+//! Writing a tree as JSON or one line per entry
+
+use std::io::{Error, Write};
+use std::path::Path;
+use crate::inspector::Disk;
+use crate::tree::{NodeId, Tree, ROOT};
+
+/// Writes every item in the tree on its own line as `size<TAB>allocated size<TAB>path`, parents before their children
+pub fn write_tsv(tree: &Tree, writer: &mut impl Write) -> Result<(), Error> {
+    return write_tsv_item(tree, ROOT, &tree.path(ROOT), writer);
+}
+
+fn write_tsv_item(tree: &Tree, id: NodeId, path: &Path, writer: &mut impl Write) -> Result<(), Error> {
+    let item = tree.item(id);
+    writeln!(writer, "{}\t{}\t{}", item.size, item.allocated_size, path.display())?;
+    for child in tree.children(id) {
+        write_tsv_item(tree, *child, &path.join(tree.name(*child)), writer)?;
+    }
+    return Ok(());
+}
+
+/// Writes the disk and the whole tree under it as JSON, it can be read back with `import::read_json`
+pub fn write_json(disk: &Disk, writer: &mut impl Write) -> Result<(), Error> {
+    serde_json::to_writer(writer, disk)?;
+    return Ok(());
+}
@@ -0,0 +1,137 @@
+use crossterm::KeyEvent;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Duration;
+use crate::terminal_helper::Backend;
+
+/// Set to write the screens the tests draw as the expected ones instead of comparing them
+const UPDATE_SCREENS_VAR: &str = "UPDATE_SCREENS";
+
+struct FakeScreen {
+    width: u16,
+    height: u16,
+    cells: Vec<Vec<char>>,
+    cursor: (u16, u16),
+    cursor_visible: bool,
+    keys: VecDeque<KeyEvent>,
+    /// What was on the screen each time a key was read
+    screens: Vec<String>,
+}
+
+/// A screen kept in memory with keys given up front, clones share the same screen so a test can keep one
+/// to look at what was drawn after giving the other to a `TerminalHelper`.
+/// Text past the end of a line carries on on the next one and anything past the bottom is dropped.
+#[derive(Clone)]
+pub struct FakeTerminal {
+    screen: Rc<RefCell<FakeScreen>>,
+}
+
+impl FakeTerminal {
+    pub fn new(width: u16, height: u16, keys: Vec<KeyEvent>) -> FakeTerminal {
+        return FakeTerminal {
+            screen: Rc::new(RefCell::new(FakeScreen {
+                width,
+                height,
+                cells: vec![vec![' '; width as usize]; height as usize],
+                cursor: (0, 0),
+                cursor_visible: true,
+                keys: keys.into_iter().collect(),
+                screens: vec![],
+            })),
+        };
+    }
+}
+
+impl FakeTerminal {
+    /// The screen as it is now, with trailing spaces taken off each line
+    pub fn text(&self) -> String {
+        let screen = self.screen.borrow();
+        return FakeTerminal::render(&screen);
+    }
+
+    /// What was on the screen each time a key was waited for, the first is what the user saw before pressing the first key
+    pub fn screens(&self) -> Vec<String> {
+        return self.screen.borrow().screens.clone();
+    }
+
+    pub fn is_cursor_visible(&self) -> bool {
+        return self.screen.borrow().cursor_visible;
+    }
+
+    fn render(screen: &FakeScreen) -> String {
+        let lines: Vec<String> = screen.cells.iter()
+            .map(|line| line.iter().collect::<String>().trim_end().to_string())
+            .collect();
+        return lines.join("\n");
+    }
+}
+
+impl Backend for FakeTerminal {
+    fn size(&self) -> (u16, u16) {
+        let screen = self.screen.borrow();
+        return (screen.width, screen.height);
+    }
+
+    fn goto(&self, x: u16, y: u16) {
+        self.screen.borrow_mut().cursor = (x, y);
+    }
+
+    fn print(&self, text: &str) {
+        let mut screen = self.screen.borrow_mut();
+        for c in text.chars() {
+            let (mut x, mut y) = screen.cursor;
+            if x >= screen.width {
+                x = 0;
+                y += 1;
+            }
+            if y < screen.height {
+                screen.cells[y as usize][x as usize] = c;
+            }
+            screen.cursor = (x + 1, y);
+        }
+    }
+
+    fn flush(&self) {}
+
+    fn clear(&self) {
+        let mut screen = self.screen.borrow_mut();
+        for line in screen.cells.iter_mut() {
+            for cell in line.iter_mut() {
+                *cell = ' ';
+            }
+        }
+    }
+
+    fn set_cursor_visible(&self, visible: bool) {
+        self.screen.borrow_mut().cursor_visible = visible;
+    }
+
+    /// Times out straight away once the keys run out, there is nothing to wait for
+    fn read_key(&self, timeout: Option<Duration>) -> Option<KeyEvent> {
+        let mut screen = self.screen.borrow_mut();
+        let text = FakeTerminal::render(&screen);
+        screen.screens.push(text);
+        match screen.keys.pop_front() {
+            Some(key) => return Some(key),
+            None if timeout.is_some() => return None,
+            None => panic!("Waiting for a key but none are left, the screen is:\n{}", screen.screens.last().unwrap()),
+        }
+    }
+}
+
+/// Compares `screen` with `tests/screens/<name>.txt`, or writes it there if `UPDATE_SCREENS` is set
+pub fn assert_screen(name: &str, screen: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("screens").join(format!("{}.txt", name));
+    if env::var_os(UPDATE_SCREENS_VAR).is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, format!("{}\n", screen)).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|err| panic!("Unable to read {}: {}, run with {}=1 to create it", path.display(), err, UPDATE_SCREENS_VAR));
+    assert!(expected.strip_suffix('\n').unwrap_or(&expected) == screen, "Screen doesn't match {}\nexpected:\n{}\nactual:\n{}", path.display(), expected, screen);
+}
@@ -0,0 +1,436 @@
+//! What a scan reads from, the disk or a tree built in memory
+
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, Metadata};
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Component, Path, PathBuf};
+use std::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How many symlinks are followed to reach a file before giving up, the same as Linux
+const MAX_LINKS: usize = 40;
+/// Files in a `MemoryFileSystem` take up a whole number of these on disk
+const BLOCK_SIZE: u64 = 4096;
+
+/// What a scan needs to know about a file, directory or symlink
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileInfo {
+    /// The apparent size
+    pub size: u64,
+    /// The space taken up on disk
+    pub allocated_size: u64,
+    /// Set for directories but not for symlinks to them
+    pub is_dir: bool,
+    /// Set if this is the symlink itself rather than what it points to
+    pub is_symlink: bool,
+    /// Device and inode, None where they aren't available such as on Windows
+    pub file_id: Option<(u64, u64)>,
+    /// Number of hard links to the file
+    pub link_count: u64,
+    /// Seconds since the Unix epoch, None if the system doesn't record it
+    pub modified: Option<u64>,
+}
+
+impl FileInfo {
+    /// The device part of `file_id`
+    pub fn device(&self) -> Option<u64> {
+        return self.file_id.map(|(device, _)| device);
+    }
+
+    /// Device and inode of files with more than one hard link
+    pub fn hard_link_id(&self) -> Option<(u64, u64)> {
+        if self.link_count > 1 && !self.is_dir {
+            return self.file_id;
+        }
+        return None;
+    }
+}
+
+impl From<&Metadata> for FileInfo {
+    #[cfg(unix)]
+    fn from(metadata: &Metadata) -> FileInfo {
+        use std::os::unix::fs::MetadataExt;
+        return FileInfo {
+            size: metadata.len(),
+            allocated_size: metadata.blocks() * 512,
+            is_dir: metadata.file_type().is_dir(),
+            is_symlink: metadata.file_type().is_symlink(),
+            file_id: Some((metadata.dev(), metadata.ino())),
+            link_count: metadata.nlink(),
+            modified: modified_secs(metadata),
+        };
+    }
+
+    #[cfg(not(unix))]
+    fn from(metadata: &Metadata) -> FileInfo {
+        return FileInfo {
+            size: metadata.len(),
+            allocated_size: metadata.len(),
+            is_dir: metadata.file_type().is_dir(),
+            is_symlink: metadata.file_type().is_symlink(),
+            file_id: None,
+            link_count: 1,
+            modified: modified_secs(metadata),
+        };
+    }
+}
+
+fn modified_secs(metadata: &Metadata) -> Option<u64> {
+    return metadata.modified().ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|since_epoch| since_epoch.as_secs());
+}
+
+/// An entry in a directory listing
+#[derive(Debug)]
+pub struct DirEntry {
+    /// The file name, without the directory
+    pub name: OsString,
+    /// The entry itself if it's a symlink, the entry can be listed but this fail if it was deleted after the listing
+    pub info: Result<FileInfo>,
+}
+
+/// Everything a scan reads, and what the browser deletes, goes through this so a scan can be run on
+/// something other than the disk. It's shared between the threads reading directories.
+pub trait FileSystem: Send + Sync {
+    /// The entries in `path` in any order, entries that couldn't be read are errors
+    fn read_dir(&self, path: &Path) -> Result<Vec<Result<DirEntry>>>;
+
+    /// Follows symlinks
+    fn metadata(&self, path: &Path) -> Result<FileInfo>;
+
+    /// Where the symlink at `path` points, as it was written
+    fn read_link(&self, path: &Path) -> Result<PathBuf>;
+
+    /// Used for ignore files
+    fn read_to_string(&self, path: &Path) -> Result<String>;
+
+    /// Deletes the file or symlink at `path`, or the directory and everything in it. Symlinks aren't followed
+    /// and directories on another device than the one `path` is in aren't entered, removing them is an error.
+    fn remove(&self, path: &Path) -> Result<()>;
+}
+
+/// The disk
+#[derive(Debug, Default)]
+pub struct RealFileSystem;
+
+impl FileSystem for RealFileSystem {
+    fn read_dir(&self, path: &Path) -> Result<Vec<Result<DirEntry>>> {
+        return Ok(fs::read_dir(path)?
+            .map(|dir_entry| {
+                let dir_entry = dir_entry?;
+                return Ok(DirEntry {
+                    name: dir_entry.file_name(),
+                    info: dir_entry.metadata().map(|metadata| FileInfo::from(&metadata)),
+                });
+            })
+            .collect());
+    }
+
+    fn metadata(&self, path: &Path) -> Result<FileInfo> {
+        return path.metadata().map(|metadata| FileInfo::from(&metadata));
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf> {
+        return path.read_link();
+    }
+
+    fn read_to_string(&self, path: &Path) -> Result<String> {
+        return fs::read_to_string(path);
+    }
+
+    fn remove(&self, path: &Path) -> Result<()> {
+        let metadata = path.symlink_metadata()?;
+        if !metadata.is_dir() {
+            return fs::remove_file(path);
+        }
+        let parent_device = match path.parent() {
+            Some(parent) => FileInfo::from(&parent.symlink_metadata()?).device(),
+            None => None,
+        };
+        return remove_dir_on_device(path, &metadata, parent_device);
+    }
+}
+
+/// Deletes the directory and everything in it, unlike `fs::remove_dir_all` this stops at directories on
+/// another device than `device` so it can't go into other filesystems mounted below the directory.
+/// Anything deleted before an error stays deleted.
+fn remove_dir_on_device(path: &Path, metadata: &Metadata, device: Option<u64>) -> Result<()> {
+    if FileInfo::from(metadata).device() != device {
+        return Err(Error::other(format!("{} is on another filesystem", path.display())));
+    }
+    for dir_entry in fs::read_dir(path)? {
+        let dir_entry = dir_entry?;
+        //doesn't follow symlinks
+        let metadata = dir_entry.metadata()?;
+        if metadata.is_dir() {
+            remove_dir_on_device(&dir_entry.path(), &metadata, device)?;
+        } else {
+            fs::remove_file(dir_entry.path())?;
+        }
+    }
+    return fs::remove_dir(path);
+}
+
+#[derive(Debug, Clone)]
+enum MemoryKind {
+    File { size: u64, allocated_size: u64, contents: String },
+    Dir,
+    Symlink(PathBuf),
+}
+
+#[derive(Debug, Clone)]
+struct MemoryNode {
+    kind: MemoryKind,
+    inode: u64,
+    /// Set for directories whose listing can't be read
+    deny_read: bool,
+    /// Set for entries that are listed but whose metadata can't be read
+    fail_metadata: bool,
+    modified: Option<u64>,
+}
+
+/// A file system kept in memory for tests and tools that want to scan something made up.
+/// Paths are absolute, parent directories are added as needed and adding a path again replaces it.
+/// Directories that can't be read or are mount points can't be removed either, nor can anything containing them,
+/// and a failed removal leaves everything in place.
+/// Every file takes up whole 4096 byte blocks on disk and directories have no size of their own.
+///
+/// ```
+/// use disk_inspector::file_system::MemoryFileSystem;
+///
+/// let mut fs = MemoryFileSystem::new();
+/// fs.add_file("/data/photos/cat.jpg", 3000);
+/// fs.add_symlink("/data/latest", "photos/cat.jpg");
+/// fs.deny_read_dir("/data/private");
+/// ```
+#[derive(Debug)]
+pub struct MemoryFileSystem {
+    /// Locked so entries can be removed through `FileSystem`
+    paths: RwLock<BTreeMap<PathBuf, MemoryNode>>,
+    /// Device of the directories everything under them is on, the root is on device 1
+    mounts: HashMap<PathBuf, u64>,
+    next_inode: u64,
+}
+
+impl MemoryFileSystem {
+    /// Just the root directory
+    pub fn new() -> MemoryFileSystem {
+        let mut fs = MemoryFileSystem {
+            paths: RwLock::new(BTreeMap::new()),
+            mounts: HashMap::new(),
+            next_inode: 1,
+        };
+        fs.add_dir("/");
+        return fs;
+    }
+}
+
+impl Default for MemoryFileSystem {
+    fn default() -> MemoryFileSystem {
+        return MemoryFileSystem::new();
+    }
+}
+
+impl MemoryFileSystem {
+    /// An empty directory, its parents are added too
+    pub fn add_dir<P: AsRef<Path>>(&mut self, path: P) {
+        self.add(path.as_ref(), MemoryKind::Dir);
+    }
+
+    /// A file of `size` bytes, which takes up whole 4K blocks on disk
+    pub fn add_file<P: AsRef<Path>>(&mut self, path: P, size: u64) {
+        self.add(path.as_ref(), MemoryKind::File { size, allocated_size: size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE, contents: String::new() });
+    }
+
+    /// A file with `contents`, such as an ignore file
+    pub fn add_text_file<P: AsRef<Path>>(&mut self, path: P, contents: &str) {
+        let size = contents.len() as u64;
+        self.add(path.as_ref(), MemoryKind::File { size, allocated_size: size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE, contents: contents.to_string() });
+    }
+
+    /// `target` can be relative to the directory the link is in, it doesn't have to exist
+    pub fn add_symlink<P: AsRef<Path>, T: AsRef<Path>>(&mut self, path: P, target: T) {
+        self.add(path.as_ref(), MemoryKind::Symlink(target.as_ref().to_path_buf()));
+    }
+
+    /// Another path to the file at `original`, which has to have been added already
+    pub fn add_hard_link<P: AsRef<Path>, O: AsRef<Path>>(&mut self, path: P, original: O) {
+        let path = path.as_ref();
+        self.add_parents(path);
+        let paths = self.paths.get_mut().unwrap();
+        let node = paths.get(original.as_ref()).expect("the original of a hard link has to be added first").clone();
+        paths.insert(path.to_path_buf(), node);
+    }
+
+    /// A directory on another device, everything added under it is on that device too
+    pub fn add_mount_point<P: AsRef<Path>>(&mut self, path: P) {
+        let path = path.as_ref();
+        self.add_dir(path);
+        let device = self.mounts.len() as u64 + 2;
+        self.mounts.insert(path.to_path_buf(), device);
+    }
+
+    /// Sets when the entry at `path`, which has to have been added already, was last changed
+    pub fn set_modified<P: AsRef<Path>>(&mut self, path: P, modified: SystemTime) {
+        let secs = modified.duration_since(UNIX_EPOCH).map(|since_epoch| since_epoch.as_secs()).unwrap_or(0);
+        self.paths.get_mut().unwrap().get_mut(path.as_ref()).expect("only entries that have been added can be changed").modified = Some(secs);
+    }
+
+    /// Reading the listing of the directory at `path` fails as if permission was denied, its own metadata can still be read
+    pub fn deny_read_dir<P: AsRef<Path>>(&mut self, path: P) {
+        let path = path.as_ref();
+        if !self.paths.get_mut().unwrap().contains_key(path) {
+            self.add_dir(path);
+        }
+        self.paths.get_mut().unwrap().get_mut(path).unwrap().deny_read = true;
+    }
+
+    /// The entry at `path` is still listed but reading its metadata fails, as if it was deleted during the scan
+    pub fn fail_metadata<P: AsRef<Path>>(&mut self, path: P) {
+        let path = path.as_ref();
+        if !self.paths.get_mut().unwrap().contains_key(path) {
+            self.add_file(path, 0);
+        }
+        self.paths.get_mut().unwrap().get_mut(path).unwrap().fail_metadata = true;
+    }
+
+    fn add(&mut self, path: &Path, kind: MemoryKind) {
+        self.add_parents(path);
+        let node = MemoryNode { kind, inode: self.next_inode, deny_read: false, fail_metadata: false, modified: None };
+        self.next_inode += 1;
+        self.paths.get_mut().unwrap().insert(path.to_path_buf(), node);
+    }
+
+    fn add_parents(&mut self, path: &Path) {
+        if let Some(parent) = path.parent() {
+            if !self.paths.get_mut().unwrap().contains_key(parent) {
+                self.add_dir(parent);
+            }
+        }
+    }
+
+    /// Follows any symlinks in the path, and the last component as well if `follow` is set
+    fn resolve(&self, path: &Path, follow: bool, links_followed: &mut usize) -> Result<PathBuf> {
+        let mut resolved = PathBuf::from("/");
+        let components: Vec<Component> = path.components().collect();
+        for (idx, component) in components.iter().enumerate() {
+            match component {
+                Component::RootDir | Component::Prefix(_) | Component::CurDir => continue,
+                Component::ParentDir => {
+                    resolved.pop();
+                    continue;
+                }
+                Component::Normal(name) => resolved.push(name),
+            }
+            let is_last = idx == components.len() - 1;
+            let target = match &self.node(&resolved)?.kind {
+                MemoryKind::Symlink(target) if follow || !is_last => target.clone(),
+                MemoryKind::File { .. } if !is_last => return Err(Error::other("Not a directory")),
+                _ => continue,
+            };
+            *links_followed += 1;
+            if *links_followed > MAX_LINKS {
+                return Err(Error::other("Too many levels of symbolic links"));
+            }
+            resolved.pop();
+            resolved = self.resolve(&resolved.join(target), true, links_followed)?;
+        }
+        return Ok(resolved);
+    }
+
+    fn node(&self, path: &Path) -> Result<MemoryNode> {
+        return self.paths
+            .read()
+            .unwrap()
+            .get(path)
+            .cloned()
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "No such file or directory"));
+    }
+
+    fn info(&self, path: &Path) -> Result<FileInfo> {
+        let node = self.node(path)?;
+        if node.fail_metadata {
+            return Err(Error::new(ErrorKind::NotFound, "No such file or directory"));
+        }
+        let link_count = self.paths.read().unwrap().values().filter(|other| other.inode == node.inode).count() as u64;
+        let device = path.ancestors().find_map(|ancestor| self.mounts.get(ancestor)).cloned().unwrap_or(1);
+        let (size, allocated_size) = match &node.kind {
+            MemoryKind::File { size, allocated_size, .. } => (*size, *allocated_size),
+            MemoryKind::Dir => (0, 0),
+            MemoryKind::Symlink(target) => (target.as_os_str().len() as u64, 0),
+        };
+        return Ok(FileInfo {
+            size,
+            allocated_size,
+            is_dir: matches!(node.kind, MemoryKind::Dir),
+            is_symlink: matches!(node.kind, MemoryKind::Symlink(_)),
+            file_id: Some((device, node.inode)),
+            link_count,
+            modified: node.modified,
+        });
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn read_dir(&self, path: &Path) -> Result<Vec<Result<DirEntry>>> {
+        let dir = self.resolve(path, true, &mut 0)?;
+        let node = self.node(&dir)?;
+        if !matches!(node.kind, MemoryKind::Dir) {
+            return Err(Error::other("Not a directory"));
+        }
+        if node.deny_read {
+            return Err(Error::new(ErrorKind::PermissionDenied, "Permission denied"));
+        }
+        let children: Vec<PathBuf> = self.paths
+            .read()
+            .unwrap()
+            .keys()
+            .filter(|child| child.parent() == Some(dir.as_path()))
+            .cloned()
+            .collect();
+        return Ok(children
+            .iter()
+            .map(|child| Ok(DirEntry {
+                name: child.file_name().map(OsStr::to_os_string).unwrap_or_default(),
+                info: self.info(child),
+            }))
+            .collect());
+    }
+
+    fn metadata(&self, path: &Path) -> Result<FileInfo> {
+        return self.info(&self.resolve(path, true, &mut 0)?);
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf> {
+        match self.node(&self.resolve(path, false, &mut 0)?)?.kind {
+            MemoryKind::Symlink(target) => return Ok(target),
+            _ => return Err(Error::new(ErrorKind::InvalidInput, "Not a symlink")),
+        }
+    }
+
+    fn read_to_string(&self, path: &Path) -> Result<String> {
+        match self.node(&self.resolve(path, true, &mut 0)?)?.kind {
+            MemoryKind::File { contents, .. } => return Ok(contents),
+            _ => return Err(Error::other("Is a directory")),
+        }
+    }
+
+    fn remove(&self, path: &Path) -> Result<()> {
+        let path = self.resolve(path, false, &mut 0)?;
+        self.node(&path)?;
+        let mut paths = self.paths.write().unwrap();
+        let removed: Vec<PathBuf> = paths.keys().filter(|other| other.starts_with(&path)).cloned().collect();
+        if removed.iter().any(|other| paths[other].deny_read) {
+            return Err(Error::new(ErrorKind::PermissionDenied, "Permission denied"));
+        }
+        if let Some(mount_point) = removed.iter().find(|other| self.mounts.contains_key(*other)) {
+            return Err(Error::other(format!("{} is on another filesystem", mount_point.display())));
+        }
+        for other in removed {
+            paths.remove(&other);
+        }
+        return Ok(());
+    }
+}
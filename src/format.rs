@@ -0,0 +1,10 @@
+//! Formatting sizes for people to read
+
+/// Formats a number of bytes with a binary unit, e.g. `1536.0` is `1.5kB`
+pub fn human_readable_bytes(bytes: f64) -> String {
+    let unit = 1024.0;
+    if bytes < unit { return format!("{}B", bytes); }
+    let exp = (bytes.ln() / unit.ln()) as usize;
+    let pre = ['k', 'M', 'G', 'T', 'P'][exp - 1];
+    return format!("{:.1}{}B", bytes / unit.powf(exp as f64), pre);
+}
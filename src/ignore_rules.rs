@@ -0,0 +1,73 @@
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use crate::file_system::FileSystem;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::sync::Arc;
+
+const IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+
+/// The rules from the ignore files in a directory and the directories above it, the nearest
+/// rules are checked first so a subdirectory can un-ignore something its parent ignores
+#[derive(Debug, Default)]
+pub struct IgnoreRules {
+    matcher: Option<Gitignore>,
+    parent: Option<Arc<IgnoreRules>>,
+}
+
+impl IgnoreRules {
+    /// Returns the rules for entries in `dir`, which are these rules plus any in the ignore files in `dir`
+    pub fn for_dir(rules: &Arc<IgnoreRules>, dir: &Path, fs: &dyn FileSystem) -> Arc<IgnoreRules> {
+        let files: Vec<_> = IGNORE_FILES.iter()
+            .map(|name| dir.join(name))
+            .filter_map(|path| match fs.read_to_string(&path) {
+                Ok(contents) => Some((path, contents)),
+                Err(err) if err.kind() == ErrorKind::NotFound => None,
+                Err(err) => {
+                    warn!("Unable to read {:?}: {}", path, err);
+                    None
+                }
+            })
+            .collect();
+        if files.is_empty() {
+            return rules.clone();
+        }
+
+        let mut builder = GitignoreBuilder::new(dir);
+        for (file, contents) in files {
+            //lines that can't be parsed are skipped, the rest of the file is still used
+            for line in contents.trim_start_matches('\u{feff}').lines() {
+                if let Err(err) = builder.add_line(Some(file.clone()), line) {
+                    warn!("Problem reading {:?}: {}", file, err);
+                }
+            }
+        }
+        match builder.build() {
+            Ok(matcher) => return Arc::new(IgnoreRules {
+                matcher: Some(matcher),
+                parent: Some(rules.clone()),
+            }),
+            Err(err) => {
+                warn!("Unable to use ignore files in {:?}: {}", dir, err);
+                return rules.clone();
+            }
+        }
+    }
+}
+
+impl IgnoreRules {
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let mut rules = Some(self);
+        while let Some(current) = rules {
+            if let Some(matcher) = &current.matcher {
+                match matcher.matched(path, is_dir) {
+                    Match::Ignore(_) => return true,
+                    Match::Whitelist(_) => return false,
+                    Match::None => {}
+                }
+            }
+            rules = current.parent.as_deref();
+        }
+        return false;
+    }
+}
@@ -0,0 +1,29 @@
+//! Reading a tree written by `export` or by `ncdu -o`
+
+use std::io::{BufRead, Error, Read};
+use serde::Deserialize;
+use crate::inspector::Disk;
+use crate::ncdu::read_ncdu;
+
+/// Reads a file written by the export command or by `ncdu -o`, ncdu files are recognised by starting with an array
+pub fn read(mut reader: impl BufRead) -> Result<Disk, Error> {
+    let is_ncdu = reader.fill_buf()?
+        .iter()
+        .find(|byte| !byte.is_ascii_whitespace())
+        .map(|byte| *byte == b'[')
+        .unwrap_or(false);
+    if is_ncdu {
+        return read_ncdu(reader);
+    }
+    return read_json(reader);
+}
+
+/// Reads a disk written by `export::write_json`
+pub fn read_json(reader: impl Read) -> Result<Disk, Error> {
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    //Every directory level is two levels of JSON so the default limit is too low for deep trees
+    deserializer.disable_recursion_limit();
+    let disk = Disk::deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    return Ok(disk);
+}
@@ -1,100 +1,275 @@
+//! Disks, the sizes of entries and running a scan
+
 use sysinfo::{SystemExt, DiskExt, System};
-use std::path::PathBuf;
-use std::fs::DirEntry;
-use crate::view::draw_dir_items;
-use crate::index_of::IndexOf;
-use std::ffi::OsStr;
-use crate::terminal_helper::TerminalHelper;
-use std::process::exit;
+use std::cmp::Reverse;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+use crate::cancel_token::CancelToken;
+use crate::file_system::{FileInfo, FileSystem};
+use crate::scan_options::ScanOptions;
+use crate::tree::{NodeId, Tree, ROOT};
+use crate::scan_error::ScanError;
+use crate::walker::Walker;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+/// A disk, or a directory being scanned on its own, and its tree
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Disk {
+    /// The disk's name, or the directory's path
     pub name: String,
+    /// Free space in bytes, 0 if it isn't known
     pub available_space: u64,
+    /// Size in bytes, 0 if it isn't known
     pub total_space: u64,
-    pub root: Arc<DiskItem>,
+    /// Just the root directory until the disk has been read
+    #[serde(rename = "root")]
+    pub tree: Arc<Tree>,
 }
 
-#[derive(Debug, Clone)]
+impl Disk {
+    /// Where the disk is mounted, or the directory
+    pub fn path(&self) -> PathBuf {
+        return self.tree.path(ROOT);
+    }
+}
+
+/// Which size of a file is shown, the apparent size is the length of the file and the
+/// allocated size is the space it takes up on disk
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SizeMode {
+    /// The length of the file
+    Apparent,
+    /// The space taken up on disk
+    Allocated,
+}
+
+impl SizeMode {
+    /// The other size mode
+    pub fn toggle(self) -> SizeMode {
+        match self {
+            SizeMode::Apparent => return SizeMode::Allocated,
+            SizeMode::Allocated => return SizeMode::Apparent,
+        }
+    }
+
+    /// How the size mode is described to users
+    pub fn name(self) -> &'static str {
+        match self {
+            SizeMode::Apparent => return "apparent size",
+            SizeMode::Allocated => return "disk usage",
+        }
+    }
+}
+
+/// Which entries are shown and counted, based on whether they're matched by an ignore file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IgnoreFilter {
+    /// Everything, whether it's ignored or not
+    All,
+    /// Only entries that aren't ignored
+    NotIgnored,
+    /// Only ignored entries
+    Ignored,
+}
+
+impl IgnoreFilter {
+    /// The filter after this one, going round all of them
+    pub fn next(self) -> IgnoreFilter {
+        match self {
+            IgnoreFilter::All => return IgnoreFilter::NotIgnored,
+            IgnoreFilter::NotIgnored => return IgnoreFilter::Ignored,
+            IgnoreFilter::Ignored => return IgnoreFilter::All,
+        }
+    }
+
+    /// How the filter is described to users
+    pub fn name(self) -> &'static str {
+        match self {
+            IgnoreFilter::All => return "everything",
+            IgnoreFilter::NotIgnored => return "not ignored",
+            IgnoreFilter::Ignored => return "ignored",
+        }
+    }
+}
+
+/// The order the entries of a directory are listed in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortMode {
+    /// Largest first, the default
+    SizeDescending,
+    /// Smallest first
+    SizeAscending,
+    /// Directories first and then by name, ignoring case
+    Name,
+    /// Entries with the most items under them first
+    ItemCount,
+    /// Most recently modified first, entries without a time last
+    Modified,
+    /// By file extension, entries without one first
+    Extension,
+}
+
+impl SortMode {
+    /// The sort mode after this one, going round all of them
+    pub fn next(self) -> SortMode {
+        match self {
+            SortMode::SizeDescending => return SortMode::SizeAscending,
+            SortMode::SizeAscending => return SortMode::Name,
+            SortMode::Name => return SortMode::ItemCount,
+            SortMode::ItemCount => return SortMode::Modified,
+            SortMode::Modified => return SortMode::Extension,
+            SortMode::Extension => return SortMode::SizeDescending,
+        }
+    }
+
+    /// How the sort mode is described to users
+    pub fn name(self) -> &'static str {
+        match self {
+            SortMode::SizeDescending => return "largest first",
+            SortMode::SizeAscending => return "smallest first",
+            SortMode::Name => return "by name",
+            SortMode::ItemCount => return "most items first",
+            SortMode::Modified => return "newest first",
+            SortMode::Extension => return "by extension",
+        }
+    }
+
+    /// Sorts entries of `tree` by the sizes `size_mode` and `filter` give, entries that compare equal
+    /// are left directories first and then by name
+    pub fn sort(self, tree: &Tree, ids: &mut [NodeId], size_mode: SizeMode, filter: IgnoreFilter) {
+        ids.sort_by(|lhs, rhs| Tree::compare(&tree.item(*lhs), tree.name(*lhs), &tree.item(*rhs), tree.name(*rhs)));
+        match self {
+            SortMode::SizeDescending => ids.sort_by_key(|id| Reverse(tree.item(*id).filtered_size(size_mode, filter))),
+            SortMode::SizeAscending => ids.sort_by_key(|id| tree.item(*id).filtered_size(size_mode, filter)),
+            SortMode::Name => {}
+            SortMode::ItemCount => ids.sort_by_key(|id| Reverse(tree.item(*id).item_count)),
+            SortMode::Modified => ids.sort_by_key(|id| Reverse(tree.item(*id).modified)),
+            SortMode::Extension => ids.sort_by_cached_key(|id| {
+                Path::new(tree.name(*id)).extension().map(|extension| extension.to_string_lossy().to_lowercase())
+            }),
+        }
+    }
+}
+
+impl Default for SortMode {
+    fn default() -> SortMode {
+        return SortMode::SizeDescending;
+    }
+}
+
+/// What is known about one file or directory, sizes of directories include everything under them
+#[derive(Debug, Clone, Default)]
 pub struct DiskItem {
-    pub path: PathBuf,
-    pub children: Vec<Arc<DiskItem>>,
-    size: u64,
-    pub files_size: u64,
+    /// The apparent size
+    pub size: u64,
+    /// The space taken up on disk
+    pub allocated_size: u64,
+    /// How much of the size is from ignored entries
+    pub ignored_size: u64,
+    /// How much of the allocated size is from ignored entries
+    pub ignored_allocated_size: u64,
+    /// If not 0 this stands for that many smaller entries that were folded together to save memory
+    pub aggregate_count: usize,
+    /// Number of entries under a directory, including everything in its subdirectories and what was folded
+    pub item_count: u64,
+    /// Seconds since the Unix epoch, the newest of the entries for entries made with `fold`
+    pub modified: Option<u64>,
+    /// Set for directories, and for symlinks to directories that were followed
     pub is_dir: bool,
-    is_symlink: bool,
-    bad_file: bool,
+    /// Set for symlinks, their size is the link's unless symlinks were followed
+    pub is_symlink: bool,
+    /// Set if this is another path to a file that has already been counted, its size isn't included in the parent's totals
+    pub is_extra_link: bool,
+    /// Set if this is a directory on another filesystem that wasn't read
+    pub is_mount_point: bool,
+    /// Set if this matched the scan's exclude rules, it wasn't read and its size isn't counted
+    pub is_excluded: bool,
+    /// Set if this is matched by an ignore file, only set if ignore files were used for the scan
+    pub is_ignored: bool,
+    /// Set if this is a directory that hasn't been completely read, because the scan is running or was cancelled
+    pub is_pending: bool,
+    /// Set if the metadata couldn't be read
+    pub bad_file: bool,
+    /// Set if this is a directory that couldn't be read or some of whose entries couldn't be read,
+    /// or an aggregate of entries like that
+    pub read_error: bool,
 }
 
 impl DiskItem {
-    fn new_root(path: PathBuf) -> DiskItem {
+    /// An empty directory
+    pub fn new_dir() -> DiskItem {
         return DiskItem {
-            path,
-            children: vec![],
-            size: 0,
-            files_size: 0,
             is_dir: true,
-            is_symlink: false,
-            bad_file: false,
+            ..DiskItem::default()
         };
     }
 
-    fn new(entry: &DirEntry) -> Result<DiskItem, std::io::Error> {
-        let path = entry.path();
-        let mut is_dir = false;
-        let mut is_symlink = false;
-        let mut size = 0;
-        let mut bad_file = false;
-        match entry.metadata() {
-            Ok(metadata) => {
-                is_symlink = metadata.file_type().is_symlink();
-                is_dir = metadata.file_type().is_dir();
-                size = metadata.len();
-            }
-            Err(_) => {
-                bad_file = true;
-            }
+    /// The entry `info` describes, with none of the flags set by a scan
+    pub fn new(info: &FileInfo) -> DiskItem {
+        return DiskItem {
+            size: info.size,
+            allocated_size: info.allocated_size,
+            is_dir: info.is_dir,
+            is_symlink: info.is_symlink,
+            modified: info.modified,
+            ..DiskItem::default()
+        };
+    }
+
+    /// Adds the sizes and count of `item` to this entry, which stands for several smaller entries
+    pub fn fold(&mut self, item: &DiskItem) {
+        if !item.is_extra_link {
+            self.size += item.size;
+            self.allocated_size += item.allocated_size;
+            self.ignored_size += item.ignored_size;
+            self.ignored_allocated_size += item.ignored_allocated_size;
         }
-        return Ok(DiskItem { path, children: vec![], size, files_size: 0, is_dir, is_symlink, bad_file });
-    }
-
-    pub fn name(&self) -> String {
-        return self.path.file_name().unwrap_or(OsStr::new("<Root>")).to_string_lossy().to_string();
-    }
-
-    fn populate(&mut self, observer: &mut impl FnMut(u64)) -> Result<(), std::io::Error> {
-        match self.path.read_dir() {
-            Ok(entries) => {
-                for entry in entries {
-                    let mut disk_item = DiskItem::new(&entry?)?;
-                    if disk_item.is_dir && !disk_item.is_symlink {
-                        disk_item.populate(observer)?;
-                    } else {
-                        if disk_item.is_symlink {}
-                    }
-                    self.children.push(Arc::new(disk_item));
-                }
-            }
-            Err(_) => { /*don't care, nothing can be done*/ }
+        self.aggregate_count += item.aggregate_count.max(1);
+        self.modified = self.modified.max(item.modified);
+        self.is_pending |= item.is_pending;
+        self.read_error |= item.read_error || item.bad_file;
+    }
+
+    /// The name used for entries made with `fold`
+    pub fn aggregate_name(&self) -> String {
+        let plural = if self.aggregate_count == 1 { "" } else { "s" };
+        return format!("{} smaller item{}", self.aggregate_count, plural);
+    }
+
+    /// The apparent or allocated size
+    pub fn size_for(&self, mode: SizeMode) -> u64 {
+        match mode {
+            SizeMode::Apparent => return self.size,
+            SizeMode::Allocated => return self.allocated_size,
+        }
+    }
+
+    /// How much of the size is from entries that aren't ignored, or are ignored, depending on `filter`
+    pub fn filtered_size(&self, mode: SizeMode, filter: IgnoreFilter) -> u64 {
+        let (size, ignored_size) = match mode {
+            SizeMode::Apparent => (self.size, self.ignored_size),
+            SizeMode::Allocated => (self.allocated_size, self.ignored_allocated_size),
+        };
+        match filter {
+            IgnoreFilter::All => return size,
+            IgnoreFilter::NotIgnored => return size.saturating_sub(ignored_size),
+            IgnoreFilter::Ignored => return ignored_size,
+        }
+    }
+
+    /// Returns false if nothing in this entry is included by `filter`
+    pub fn is_shown(&self, filter: IgnoreFilter) -> bool {
+        match filter {
+            IgnoreFilter::All => return true,
+            IgnoreFilter::NotIgnored => return !self.is_ignored,
+            IgnoreFilter::Ignored => return self.is_ignored || self.ignored_size > 0 || self.ignored_allocated_size > 0,
         }
-        self.size += self.children
-            .iter()
-            .fold(0, |acc, child| acc + child.size);
-        self.files_size = self.children
-            .iter()
-            .filter(|child| !child.is_dir && !child.is_symlink)
-            .fold(0, |acc, child| acc + child.size);
-        self.children.sort_by(|lhs, rhs| {
-            let lhs_file = lhs.path.file_name().unwrap_or(OsStr::new("")).to_string_lossy().to_string().to_lowercase();
-            let rhs_file = rhs.path.file_name().unwrap_or(OsStr::new("")).to_string_lossy().to_string().to_lowercase();
-            lhs.is_dir.cmp(&rhs.is_dir).reverse().then(lhs_file.cmp(&rhs_file))
-        });
-        observer(self.files_size);
-        Ok(())
     }
 }
 
+/// The mounted disks, each with a tree that only has the mount point in it
 pub fn get_all_disks(system: System) -> Vec<Disk> {
     return system.get_disks()
         .iter()
@@ -102,92 +277,209 @@ pub fn get_all_disks(system: System) -> Vec<Disk> {
             name: disk.get_name().to_string_lossy().into_owned(),
             available_space: disk.get_available_space(),
             total_space: disk.get_total_space(),
-            root: Arc::new(DiskItem::new_root(disk.get_mount_point().to_owned())),
+            tree: Arc::new(Tree::new_root(disk.get_mount_point())),
         })
         .collect();
 }
 
+/// How far a read has got, sent to the status observer while reading
+#[derive(Debug, Clone, Default)]
+pub struct ScanProgress {
+    /// Never reaches 100 until the read has finished
+    pub percentage: usize,
+    /// Files read so far
+    pub files: usize,
+    /// Directories read so far
+    pub dirs: usize,
+    /// Allocated size of everything read so far
+    pub bytes: u64,
+    /// The directory most recently started
+    pub current_path: PathBuf,
+    /// Files and directories read per second since the read started
+    pub items_per_second: f64,
+    /// None until enough has been read to make a guess
+    pub time_left: Option<Duration>,
+}
+
+/// What is happening in a read, sent over the channel given to `Inspector`.
+/// `Started` is always the first event and `Finished` the last, even if the read was cancelled.
+#[derive(Debug, Clone)]
 pub enum Status {
-    Reading { percentage: usize },
-    Done,
+    /// The read of `path` has started
+    Started {
+        /// The root of the read
+        path: PathBuf,
+    },
+    /// Sent regularly while reading
+    Reading(ScanProgress),
+    /// A directory is about to be read
+    DirEntered {
+        /// The directory
+        path: PathBuf,
+    },
+    /// A directory and everything under it has been read, the sizes include every path to hard linked files
+    DirFinished {
+        /// The directory
+        path: PathBuf,
+        /// The apparent size of the directory and everything under it
+        size: u64,
+        /// The space the directory and everything under it takes up on disk
+        allocated_size: u64,
+    },
+    /// Something couldn't be read, the read carries on without it
+    Error(ScanError),
+    /// The read was stopped before everything was read, `Finished` follows
+    Cancelled,
+    /// The read is over, nothing is sent after this
+    Finished(ScanSummary),
+}
+
+/// Totals for a finished read
+#[derive(Debug, Clone, Default)]
+pub struct ScanSummary {
+    /// Files read, including symlinks
+    pub files: usize,
+    /// Directories read, including the root
+    pub dirs: usize,
+    /// The apparent size of the root
+    pub size: u64,
+    /// The space the root takes up on disk
+    pub allocated_size: u64,
+    /// How many things couldn't be read
+    pub errors: usize,
+    /// How long the read took
+    pub elapsed: Duration,
 }
 
-pub struct Inspector<F: FnMut(Status)> {
-    total_used_space: u64,
-    bytes_counted: u64,
-    status_observer: F,
+/// Runs scans and sends what is happening over a channel
+pub struct Inspector {
+    total_used_space: Option<u64>,
+    options: ScanOptions,
+    events: Sender<Status>,
 }
 
-impl<F> Inspector<F> where F: FnMut(Status) -> () {
-    pub fn new(total_used_space: u64, observer: F) -> Inspector<F> {
+impl Inspector {
+    /// Progress is based on how much of the used space has been counted.
+    /// Events are dropped if the receiver has gone.
+    pub fn new(total_used_space: u64, options: ScanOptions, events: Sender<Status>) -> Inspector {
         Inspector {
-            total_used_space,
-            bytes_counted: 0,
-            status_observer: observer,
+            total_used_space: Some(total_used_space),
+            options,
+            events,
         }
     }
 
-    pub fn populate(&mut self, disk_root_path: PathBuf) -> Result<DiskItem, std::io::Error> {
-        let bytes_counted = &mut (self.bytes_counted as f64);
-        let total_used_space = self.total_used_space as f64;
-        let observer = &mut self.status_observer;
-        let mut disk_item = DiskItem::new_root(disk_root_path);
-        disk_item.populate(&mut |bytes| {
-            *bytes_counted += bytes as f64;
-            observer(Status::Reading { percentage: ((*bytes_counted / total_used_space) * 100_f64) as usize });
-        })?;
-        (self.status_observer)(Status::Done);
-        Ok(disk_item)
+    /// Progress is based on how many of the directories found so far have been read,
+    /// for scanning a directory that isn't the root of a disk
+    pub fn for_directory(options: ScanOptions, events: Sender<Status>) -> Inspector {
+        Inspector {
+            total_used_space: None,
+            options,
+            events,
+        }
+    }
+
+    /// Reads everything under `disk_root_path`
+    pub fn populate(&mut self, disk_root_path: PathBuf) -> Tree {
+        let scan = LiveScan::new(disk_root_path, &self.options, Arc::new(CancelToken::new()));
+        return self.populate_with(&scan);
+    }
+
+    /// Runs `scan`, which allows it to be shared with other threads to look at the tree while it is
+    /// being read or to read from another file system. The options it was made with are used instead of the inspector's.
+    /// Anything that couldn't be read is listed in the tree's errors.
+    pub fn populate_with(&mut self, scan: &LiveScan) -> Tree {
+        let walker = &scan.walker;
+        let total_used_space = self.total_used_space.filter(|space| *space > 0);
+        let events = &self.events;
+        let started = Instant::now();
+        let mut last_fraction = 0_f64;
+        let _ = events.send(Status::Started { path: walker.root().to_path_buf() });
+        let mut last_progress = None;
+        let tree = walker.walk(events, |progress| {
+            let fraction = match total_used_space {
+                Some(total_used_space) => progress.bytes_counted as f64 / total_used_space as f64,
+                None => progress.dirs_read as f64 / progress.dirs_found as f64,
+            };
+            //directories are found as they are read so the fraction can go down, and the used space
+            //includes things that aren't counted (or the files are bigger than the disk says) so it can go over 1
+            last_fraction = last_fraction.max(fraction).min(0.99);
+            let elapsed = started.elapsed();
+            let items = progress.files_read + progress.dirs_read;
+            let time_left = if last_fraction > 0.01 && elapsed >= MIN_ESTIMATE_TIME {
+                Some(elapsed.mul_f64((1_f64 - last_fraction) / last_fraction))
+            } else {
+                None
+            };
+            let _ = events.send(Status::Reading(ScanProgress {
+                percentage: (last_fraction * 100_f64) as usize,
+                files: progress.files_read,
+                dirs: progress.dirs_read,
+                bytes: progress.bytes_counted,
+                current_path: progress.current_path.clone(),
+                items_per_second: items as f64 / elapsed.as_secs_f64().max(0.001),
+                time_left,
+            }));
+            last_progress = Some(progress);
+        });
+        if walker.is_cancelled() {
+            let _ = self.events.send(Status::Cancelled);
+        }
+        let root = tree.item(ROOT);
+        let _ = self.events.send(Status::Finished(ScanSummary {
+            files: last_progress.as_ref().map(|progress| progress.files_read).unwrap_or(0),
+            dirs: last_progress.as_ref().map(|progress| progress.dirs_read).unwrap_or(0),
+            size: root.size,
+            allocated_size: root.allocated_size,
+            errors: tree.errors().len(),
+            elapsed: started.elapsed(),
+        }));
+        return tree;
     }
 }
 
-pub struct DirNav {
-    disk: Box<Disk>,
+/// A scan that can be looked at from other threads while `Inspector::populate_with` runs it
+pub struct LiveScan {
+    walker: Walker,
 }
 
-impl DirNav {
-    pub fn new(disk: Disk) -> DirNav {
-        return DirNav {
-            disk: Box::new(disk),
-        };
+impl LiveScan {
+    /// Reads `root` from the disk, the scan stops early if `cancel_token` is cancelled
+    pub fn new(root: PathBuf, options: &ScanOptions, cancel_token: Arc<CancelToken>) -> LiveScan {
+        return LiveScan { walker: Walker::new(root, options, cancel_token) };
+    }
+
+    /// Reads `root` from `fs` instead of the disk
+    pub fn with_file_system(root: PathBuf, options: &ScanOptions, cancel_token: Arc<CancelToken>, fs: Arc<dyn FileSystem>) -> LiveScan {
+        return LiveScan { walker: Walker::with_file_system(root, options, cancel_token, fs) };
     }
 }
 
-impl DirNav {
-    //This needs to be fixed, it definitely will cause a stack overflow eventually
-    pub fn navigate_directory(&self, terminal_helper: &TerminalHelper, current_dir: Arc<DiskItem>, mut parents: Vec<Arc<DiskItem>>) {
-        terminal_helper.clear_screen();
-
-        let parent_names: Vec<String> = parents.iter().map(|item| format!("'{}'", item.name())).collect();
-        debug!("Path: {}", parent_names.join("/"));
-
-        let item_names: Vec<(String, u64, bool)> = current_dir.children
-            .iter()
-            .map(|item| (item.name(), item.size, item.is_dir))
-            .collect();
-        let title = current_dir.path.to_string_lossy().to_string();
-        let show_go_up = !parents.is_empty();
-
-        debug!("Navigating {} with {} children", title, current_dir.children.len());
-
-        match draw_dir_items(terminal_helper, title, show_go_up, item_names) {
-            None => {
-                terminal_helper.teardown();
-                exit(0);
-            }
-            Some(selected) => {
-                if selected == ".." {
-                    let new_dir = parents.pop().unwrap();
-                    debug!("Removed {}", new_dir.name());
-                    self.navigate_directory(terminal_helper, new_dir, parents);
-                } else {
-                    let idx = current_dir.children.index_of(|item| item.name() == selected).unwrap();
-                    let new_dir = &current_dir.children[idx];
-                    debug!("Adding {}", current_dir.name());
-                    parents.push(current_dir.clone());
-                    self.navigate_directory(terminal_helper, new_dir.clone(), parents);
-                }
-            }
-        }
+impl LiveScan {
+    /// The root directory and its children as they are now, see `snapshot`. None once the scan has finished.
+    pub fn root_snapshot(&self) -> Option<Tree> {
+        return self.walker.snapshot(self.walker.root_id());
+    }
+
+    /// The directory `id` of a snapshot from this scan with its children as they are now. The children that
+    /// are directories have no children of their own but can be passed to this method with the new snapshot.
+    /// None once the scan has finished, or if `id` isn't a directory that is being read.
+    ///
+    /// Hard links are only found once the scan has finished so may be counted more than once.
+    pub fn snapshot(&self, tree: &Tree, id: NodeId) -> Option<Tree> {
+        return tree.scan_id(id).and_then(|scan_id| self.walker.snapshot(scan_id));
+    }
+
+    /// What couldn't be read so far, in the order it was found
+    pub fn errors(&self) -> Vec<ScanError> {
+        return self.walker.errors();
+    }
+
+    /// The number of `errors` so far
+    pub fn error_count(&self) -> usize {
+        return self.walker.error_count();
     }
 }
+
+/// How long a read has to run before the time left is estimated
+const MIN_ESTIMATE_TIME: Duration = Duration::from_secs(2);
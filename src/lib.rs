@@ -0,0 +1,53 @@
+//! Reads how much space directories take up and what's in them.
+//!
+//! A scan is run by an [`Inspector`](inspector/struct.Inspector.html), which reads a directory
+//! on several threads and returns a [`Tree`](tree/struct.Tree.html) with the size of every entry.
+//! Progress, errors and the end of the scan are sent as [`Status`](inspector/enum.Status.html)
+//! events over a channel. The tree can then be searched with [`analysis`](analysis/index.html),
+//! written out with [`export`](export/index.html) or [`ncdu`](ncdu/index.html) and read back with
+//! [`import`](import/index.html).
+//!
+//! Scans read through a [`FileSystem`](file_system/trait.FileSystem.html), which is the disk unless
+//! a [`LiveScan`](inspector/struct.LiveScan.html) is made with another one such as the in-memory
+//! [`MemoryFileSystem`](file_system/struct.MemoryFileSystem.html). A `LiveScan` can also be shared
+//! with other threads to look at what has been read while the scan is running.
+//!
+//! ```no_run
+//! use disk_inspector::format::human_readable_bytes;
+//! use disk_inspector::inspector::{Inspector, SizeMode};
+//! use disk_inspector::scan_options::ScanOptions;
+//! use disk_inspector::tree::ROOT;
+//! use std::path::PathBuf;
+//! use std::sync::mpsc;
+//!
+//! let (events, _receiver) = mpsc::channel();
+//! let tree = Inspector::for_directory(ScanOptions::new(), events).populate(PathBuf::from("/home"));
+//! for child in tree.children(ROOT) {
+//!     let size = tree.item(*child).size_for(SizeMode::Apparent);
+//!     println!("{} {}", tree.name(*child), human_readable_bytes(size as f64));
+//! }
+//! ```
+//!
+//! The `disk-inspector` binary is built on this, with a terminal UI for browsing the tree.
+
+#![allow(clippy::needless_return)]
+#![warn(missing_docs)]
+
+#[macro_use]
+extern crate log;
+
+pub mod analysis;
+pub mod cancel_token;
+pub mod export;
+pub mod file_system;
+pub mod format;
+pub mod import;
+pub mod inspector;
+pub mod ncdu;
+pub mod scan_error;
+pub mod scan_filter;
+pub mod scan_options;
+pub mod tree;
+mod ignore_rules;
+mod walker;
+mod work_queue;
@@ -1,41 +1,95 @@
+#![allow(clippy::needless_return)]
+
 extern crate sysinfo;
 #[macro_use]
 extern crate log;
 extern crate simplelog;
+extern crate clap;
 
 mod app;
-mod inspector;
+mod cli;
+mod config;
+mod commands;
+mod dir_nav;
+#[cfg(test)]
+mod fake_terminal;
 mod view;
-mod index_of;
 mod terminal_helper;
-mod atomic_counter;
 
 use simplelog::*;
 use std::fs::File;
-use crate::app::App;
-use crate::inspector::{DirNav, Disk};
+use std::io::BufReader;
+use crate::app::{App, Scan};
+use crate::dir_nav::DirNav;
+use disk_inspector::import;
+use disk_inspector::inspector::Disk;
 use crate::view::*;
-use std::error::Error;
 use crate::terminal_helper::TerminalHelper;
 use std::process::exit;
-use crate::atomic_counter::AtomicCounter;
 use std::sync::Arc;
-use crossterm::RawScreen;
-
-fn main() -> Result<(), std::io::Error> {
-    WriteLogger::init(LevelFilter::Debug, Config::default(), File::create("di.log").unwrap()).unwrap();
+use std::path::PathBuf;
+use crate::cli::{Cli, Command};
+use crossterm::{KeyEvent, RawScreen};
+use std::time::Duration;
+use disk_inspector::scan_options::ScanOptions;
+
+/// How often the progress screen is redrawn
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+fn main() {
+    let cli = Cli::parse();
+
+    //a log that can't be written isn't a reason to stop, such as when run from a read-only directory
+    match &cli.log_file {
+        Some(log_file) if cli.log_level != LevelFilter::Off => match File::create(log_file) {
+            Ok(file) => WriteLogger::init(cli.log_level, Config::default(), file).unwrap(),
+            Err(err) => eprintln!("Unable to create log file {}, carrying on without a log: {}", log_file.display(), err),
+        },
+        _ => {}
+    }
 
     debug!("Starting up");
 
     let app = App::new();
 
-    let raw = RawScreen::into_raw_mode();
+    let result = match cli.command {
+        Command::Tui { path, options, live, dry_run } => run_tui(app, path, options, live, dry_run),
+        Command::Scan { path, options, summary } => commands::print_summary(path, options, summary),
+        Command::Disks => commands::print_disks(&app),
+        Command::Export { path, output, format, options } => commands::export(&app, path, output, format, options),
+        Command::Import { file } => run_import(file),
+    };
+
+    if let Err(err) = result {
+        error!("{}", err);
+        eprintln!("{}", err);
+        exit(1);
+    }
+}
+
+fn run_tui(app: App, path: Option<PathBuf>, options: ScanOptions, live: bool, dry_run: bool) -> Result<(), std::io::Error> {
+    //If a directory is given it's scanned directly, otherwise the user picks a disk
+    let directory = match path {
+        None => None,
+        Some(path) => Some(app.disk_for_path(path)?),
+    };
+
+    let _raw = RawScreen::into_raw_mode();
     let terminal_helper = TerminalHelper::new();
 
     terminal_helper.setup();
 
     terminal_helper.clear_screen();
 
+    if let Some(disk) = directory {
+        debug!("Reading directory {}", disk.name);
+        let scan = app.read_directory_sizes(disk.path(), options.clone());
+        if read_and_navigate(&terminal_helper, disk, scan, live, dry_run) {
+            terminal_helper.teardown();
+            return Ok(());
+        }
+    }
+
     terminal_helper.show_dialog("Gathering system info");
 
     debug!("Getting disk info");
@@ -44,76 +98,121 @@ fn main() -> Result<(), std::io::Error> {
         Ok(disk_list) => {
             debug!("{} disks found", disk_list.len());
             let disks = disk_list;
-            let disk_info_list = disks.iter().map(|disk| (disk.name.clone(), disk.available_space, disk.total_space)).collect();
+            let disk_info_list: Vec<(String, u64, u64)> = disks.iter().map(|disk| (disk.name.clone(), disk.available_space, disk.total_space)).collect();
+
+            loop {
+                match show_disk_list(&terminal_helper, disk_info_list.clone()) {
+                    None => {
+                        debug!("Exiting at disk list");
+                        terminal_helper.teardown();
+                        exit(0);
+                    }
+                    Some(selected) => {
+                        debug!("Selected {}", &selected);
+                        let mut disk = disks.get(selected).unwrap().clone();
 
-            match show_disk_list(&terminal_helper, disk_info_list) {
-                None => {
-                    debug!("Exiting at disk list");
-                    terminal_helper.teardown();
-                    exit(0);
+                        let scan = app.read_file_sizes(&mut disk, options.clone());
+
+                        if read_and_navigate(&terminal_helper, disk, scan, live, dry_run) {
+                            break;
+                        }
+                    }
                 }
-                Some(selected) => {
-                    debug!("Selected {}", &selected);
-                    let mut disk = disks.get(selected).unwrap().clone();
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!("{}", err.raw_os_error().unwrap_or(-1));
+            eprintln!("{:?}", err.kind());
+        }
+    }
 
-                    terminal_helper.clear_screen();
-                    terminal_helper.show_dialog("Getting all file sizes");
+    terminal_helper.teardown();
 
-                    let progress = Arc::new(AtomicCounter::new());
+    Ok(())
+}
 
-                    let child = app.read_file_sizes(&mut disk, progress.clone());
+/// Lets the user browse a tree written by the export command or ncdu, nothing can be deleted from it
+fn run_import(file: PathBuf) -> Result<(), std::io::Error> {
+    debug!("Importing {:?}", file);
+    let disk = import::read(BufReader::new(File::open(file)?))?;
 
-                    let mut last_printed = 0;
+    let _raw = RawScreen::into_raw_mode();
+    let terminal_helper = TerminalHelper::new();
 
-                    terminal_helper.clear_screen();
+    terminal_helper.setup();
 
-                    loop {
-                        let progress_value = progress.get();
-                        if last_printed != progress_value {
-                            last_printed = progress_value;
-                            terminal_helper.draw_progress("Reading files", last_printed);
-                        }
-                        if progress_value >= 100 {
-                            debug!("Read complete");
-                            break;
-                        }
-                    }
+    let mut nav_dir = DirNav::new(disk);
+    nav_dir.set_imported(true);
+    nav_dir.navigate_directory(&terminal_helper);
 
-                    let x = child.join().expect("join failed");
-                    let filled_root = Arc::new(x.expect(""));
+    terminal_helper.teardown();
 
-                    debug!("Thread joined");
+    Ok(())
+}
+
+/// Shows the progress of the read and then lets the user browse the result,
+/// or if `live` is set lets the user browse straight away.
+/// Returns false if the user stopped the read to pick another disk.
+fn read_and_navigate(terminal_helper: &TerminalHelper, disk: Disk, scan: Scan, live: bool, dry_run: bool) -> bool {
+    if live {
+        let mut nav_dir = DirNav::new_live(disk, scan);
+        nav_dir.set_dry_run(dry_run);
+        nav_dir.navigate_directory(terminal_helper);
+        return true;
+    }
 
-                    let new_disk = Disk {
-                        name: disk.name.clone(),
-                        available_space: disk.available_space,
-                        total_space: disk.total_space,
-                        root: filled_root.clone(),
-                    };
+    terminal_helper.clear_screen();
+    terminal_helper.show_dialog("Getting all file sizes");
 
-                    let nav_dir: DirNav = DirNav::new(new_disk);
+    terminal_helper.clear_screen();
 
-                    nav_dir.navigate_directory(&terminal_helper, filled_root.clone(), vec![]);
+    loop {
+        if scan.thread.is_finished() {
+            debug!("Read complete");
+            break;
+        }
+        draw_scan_progress(terminal_helper, &scan.progress.lock().unwrap().clone());
+        if terminal_helper.wait_for_key(vec![KeyEvent::Esc], Some(PROGRESS_INTERVAL)).is_some() {
+            match show_cancel_menu(terminal_helper) {
+                CancelAction::KeepReading => {
+                    terminal_helper.clear_screen();
+                }
+                CancelAction::Browse => {
+                    debug!("Read cancelled, browsing partial tree");
+                    scan.cancel_token.cancel();
+                    terminal_helper.show_dialog("Stopping");
+                    break;
+                }
+                CancelAction::PickDisk => {
+                    debug!("Read cancelled");
+                    scan.cancel_token.cancel();
+                    terminal_helper.show_dialog("Stopping");
+                    let _ = scan.thread.join();
+                    return false;
                 }
             }
         }
-        Err(err) => {
-            eprintln!("{}", err.description());
-            eprintln!("{}", err.raw_os_error().unwrap_or(-1));
-            eprintln!("{:?}", err.kind());
-        }
     }
 
-    terminal_helper.teardown();
+    let filled_tree = Arc::new(scan.thread.join().expect("join failed"));
 
-    Ok(())
+    debug!("Thread joined");
+
+    let new_disk = Disk {
+        name: disk.name.clone(),
+        available_space: disk.available_space,
+        total_space: disk.total_space,
+        tree: filled_tree,
+    };
+
+    navigate(terminal_helper, new_disk, dry_run);
+    return true;
 }
 
+fn navigate(terminal_helper: &TerminalHelper, disk: Disk, dry_run: bool) {
+    let mut nav_dir: DirNav = DirNav::new(disk);
+    nav_dir.set_dry_run(dry_run);
 
-pub fn human_readable_bytes(bytes: f64) -> String {
-    let unit = 1024.0;
-    if bytes < unit { return format!("{}B", bytes); }
-    let exp = (bytes.ln() / unit.ln()) as usize;
-    let pre = ['k', 'M', 'G', 'T', 'P'][(exp as usize) - 1];
-    return format!("{:.1}{}B", bytes / unit.powf(exp as f64), pre);
-}
\ No newline at end of file
+    nav_dir.navigate_directory(terminal_helper);
+}
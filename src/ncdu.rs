@@ -0,0 +1,261 @@
+//! ncdu's JSON dump format
+
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{Error, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde::de::{DeserializeSeed, Error as _, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::de::value::MapAccessDeserializer;
+use crate::inspector::{Disk, DiskItem};
+use crate::tree::{NodeId, Tree, ROOT};
+
+/// ncdu's export format is `[major, minor, header, root]` where a directory is an array of its own
+/// entry followed by its children and everything else is just an entry
+const MAJOR_VERSION: u64 = 1;
+const MINOR_VERSION: u64 = 1;
+/// How deep directories can be nested when reading, deeper than they get in practice
+/// but shallow enough that reading doesn't overflow the stack of a thread
+pub const MAX_DEPTH: usize = 256;
+
+const EXCLUDED_OTHER_FS: &str = "otherfs";
+const EXCLUDED_PATTERN: &str = "pattern";
+
+#[derive(Serialize)]
+struct Header {
+    progname: &'static str,
+    progver: &'static str,
+    timestamp: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    name: String,
+    #[serde(default)]
+    asize: u64,
+    #[serde(default)]
+    dsize: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dev: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ino: Option<u64>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    hlnkc: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    read_error: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    notreg: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    excluded: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mtime: Option<u64>,
+}
+
+fn is_false(value: &bool) -> bool {
+    return !value;
+}
+
+/// Writes the tree under the disk in the format `ncdu -o` uses, so it can be opened with `ncdu -f`.
+/// ncdu has no way to show entries that were folded together, so they are left out and their sizes
+/// are counted as part of their directory's own size instead.
+pub fn write_ncdu(disk: &Disk, writer: &mut impl Write) -> Result<(), Error> {
+    let header = Header {
+        progname: env!("CARGO_PKG_NAME"),
+        progver: env!("CARGO_PKG_VERSION"),
+        timestamp: chrono::Utc::now().timestamp(),
+    };
+    write!(writer, "[{},{},", MAJOR_VERSION, MINOR_VERSION)?;
+    serde_json::to_writer(&mut *writer, &header)?;
+    write!(writer, ",")?;
+    write_item(&disk.tree, ROOT, writer)?;
+    write!(writer, "]")?;
+    return Ok(());
+}
+
+fn write_item(tree: &Tree, id: NodeId, writer: &mut impl Write) -> Result<(), Error> {
+    let disk_item = tree.item(id);
+    //ncdu stores the size of the directory itself rather than the total
+    let (asize, dsize) = if disk_item.is_dir {
+        tree.children(id)
+            .iter()
+            .map(|child| tree.item(*child))
+            .filter(|child| !child.is_extra_link && child.aggregate_count == 0)
+            .fold((disk_item.size, disk_item.allocated_size), |(asize, dsize), child| {
+                (asize.saturating_sub(child.size), dsize.saturating_sub(child.allocated_size))
+            })
+    } else {
+        (disk_item.size, disk_item.allocated_size)
+    };
+    let file_id = tree.hard_link(id);
+    let entry = Entry {
+        name: tree.name(id).to_string(),
+        asize,
+        dsize,
+        dev: file_id.map(|(dev, _)| dev),
+        ino: file_id.map(|(_, ino)| ino),
+        hlnkc: file_id.is_some(),
+        read_error: disk_item.bad_file || disk_item.read_error,
+        notreg: disk_item.is_symlink,
+        excluded: if disk_item.is_mount_point {
+            Some(EXCLUDED_OTHER_FS.to_string())
+        } else if disk_item.is_excluded {
+            Some(EXCLUDED_PATTERN.to_string())
+        } else {
+            None
+        },
+        mtime: disk_item.modified,
+    };
+
+    if disk_item.is_dir && !disk_item.is_mount_point && !disk_item.is_excluded {
+        write!(writer, "[")?;
+        serde_json::to_writer(&mut *writer, &entry)?;
+        for child in tree.children(id).iter().filter(|child| tree.item(**child).aggregate_count == 0) {
+            write!(writer, ",")?;
+            write_item(tree, *child, writer)?;
+        }
+        write!(writer, "]")?;
+    } else {
+        serde_json::to_writer(&mut *writer, &entry)?;
+    }
+    return Ok(());
+}
+
+/// Reads a file written by `ncdu -o`, ncdu doesn't record anything about the disk so the space figures are 0.
+/// The file is read as it's parsed rather than loaded as a whole, and directories nested more than
+/// `MAX_DEPTH` deep are an error rather than overflowing the stack.
+pub fn read_ncdu(reader: impl Read) -> Result<Disk, Error> {
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    //The depth is limited by `NodeSeed` instead, serde_json's limit is too low for deep trees
+    deserializer.disable_recursion_limit();
+    let root = deserializer.deserialize_seq(ExportVisitor)?;
+    deserializer.end()?;
+
+    //The root's name is its full path
+    let mut tree = Tree::new(Path::new(&root.name), root.item);
+    add_children(&mut tree, ROOT, root.children, &mut HashSet::new());
+
+    return Ok(Disk {
+        name: root.name,
+        available_space: 0,
+        total_space: 0,
+        tree: Arc::new(tree),
+    });
+}
+
+/// Children are added in sorted order so the same hard link is always counted, as when scanning
+fn add_children(tree: &mut Tree, id: NodeId, mut children: Vec<ReadNode>, seen_files: &mut HashSet<(u64, u64)>) {
+    children.sort_by(|lhs, rhs| Tree::compare(&lhs.item, &lhs.name, &rhs.item, &rhs.name));
+    let ids = tree.add_children(id, children.iter().map(|child| (child.name.as_str(), child.item.clone())).collect());
+    for (child, child_id) in children.into_iter().zip(ids) {
+        add_children(tree, child_id, child.children, seen_files);
+        if let Some(file_id) = child.file_id {
+            let mut item = tree.item(child_id);
+            item.is_extra_link = !seen_files.insert(file_id);
+            tree.set_item(child_id, item);
+            tree.set_hard_link(child_id, file_id);
+        }
+    }
+    tree.update_totals(id);
+}
+
+/// An entry read from the file, kept until the whole file has been read so that hard links can be counted in sorted order
+struct ReadNode {
+    name: String,
+    item: DiskItem,
+    dev: u64,
+    /// The device and inode if it's a hard link
+    file_id: Option<(u64, u64)>,
+    children: Vec<ReadNode>,
+}
+
+/// Reads `[major, minor, header, root]` and returns the root
+struct ExportVisitor;
+
+impl<'de> Visitor<'de> for ExportVisitor {
+    type Value = ReadNode;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        return formatter.write_str("an ncdu export");
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<ReadNode, A::Error> {
+        let major: u64 = seq.next_element()?.ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        if major != MAJOR_VERSION {
+            return Err(A::Error::custom("unsupported ncdu export version"));
+        }
+        for index in 1..3 {
+            seq.next_element::<IgnoredAny>()?.ok_or_else(|| A::Error::invalid_length(index, &self))?;
+        }
+        let root = seq.next_element_seed(NodeSeed { parent_dev: 0, depth: 0 })?.ok_or_else(|| A::Error::invalid_length(3, &self))?;
+        //later versions may add more
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        return Ok(root);
+    }
+}
+
+/// Reads a directory, which is an array of its own entry followed by its children, or any other entry.
+/// `parent_dev` is used if the entry doesn't have a device of its own
+struct NodeSeed {
+    parent_dev: u64,
+    depth: usize,
+}
+
+impl<'de> DeserializeSeed<'de> for NodeSeed {
+    type Value = ReadNode;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<ReadNode, D::Error> {
+        return deserializer.deserialize_any(self);
+    }
+}
+
+impl<'de> Visitor<'de> for NodeSeed {
+    type Value = ReadNode;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        return formatter.write_str("a directory or file entry");
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<ReadNode, A::Error> {
+        let entry = Entry::deserialize(MapAccessDeserializer::new(map))?;
+        return Ok(read_entry(entry, false, self.parent_dev));
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<ReadNode, A::Error> {
+        if self.depth >= MAX_DEPTH {
+            return Err(A::Error::custom(format!("directories are nested more than {} deep", MAX_DEPTH)));
+        }
+        let entry: Entry = seq.next_element()?.ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        let mut node = read_entry(entry, true, self.parent_dev);
+        while let Some(child) = seq.next_element_seed(NodeSeed { parent_dev: node.dev, depth: self.depth + 1 })? {
+            node.children.push(child);
+        }
+        return Ok(node);
+    }
+}
+
+fn read_entry(entry: Entry, is_dir: bool, parent_dev: u64) -> ReadNode {
+    let is_mount_point = entry.excluded.as_ref().map(|reason| reason == EXCLUDED_OTHER_FS || reason == "othfs").unwrap_or(false);
+
+    let item = DiskItem {
+        size: entry.asize,
+        allocated_size: entry.dsize,
+        is_dir: is_dir || is_mount_point,
+        is_mount_point,
+        is_excluded: entry.excluded.as_ref().map(|reason| reason == EXCLUDED_PATTERN).unwrap_or(false),
+        //ncdu doesn't say what kind of file it is, symlinks are the only kind disk-inspector marks
+        is_symlink: entry.notreg && !is_dir,
+        //ncdu uses the same flag for directories that couldn't be read and files whose metadata couldn't be read
+        bad_file: entry.read_error && !is_dir,
+        read_error: entry.read_error && is_dir,
+        modified: entry.mtime,
+        ..DiskItem::default()
+    };
+    let dev = entry.dev.unwrap_or(parent_dev);
+    let file_id = if entry.hlnkc && !is_dir {
+        entry.ino.map(|ino| (dev, ino))
+    } else {
+        None
+    };
+    return ReadNode { name: entry.name, item, dev, file_id, children: vec![] };
+}
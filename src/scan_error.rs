@@ -0,0 +1,34 @@
+//! Entries that could not be read during a scan
+
+use std::fmt;
+use std::io::Error;
+use std::path::PathBuf;
+
+/// Something that couldn't be read during a scan, such as a directory without permission
+/// or a file that was deleted while it was being read. The scan carries on without it.
+#[derive(Debug, Clone)]
+pub struct ScanError {
+    /// What couldn't be read
+    pub path: PathBuf,
+    /// What was being done, e.g. "read directory"
+    pub action: &'static str,
+    /// Why, from the operating system
+    pub message: String,
+}
+
+impl ScanError {
+    /// `action` describes what was being done to `path` when `err` happened
+    pub fn new(path: PathBuf, action: &'static str, err: &Error) -> ScanError {
+        return ScanError {
+            path,
+            action,
+            message: err.to_string(),
+        };
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "Unable to {} {}: {}", self.action, self.path.display(), self.message);
+    }
+}
@@ -0,0 +1,47 @@
+//! Include and exclude globs
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+/// Glob rules for what a scan skips. A pattern is checked against both the full path and the
+/// name of an entry, so `node_modules` skips every directory with that name and `/proc` only that one.
+#[derive(Debug, Clone, Default)]
+pub struct ScanFilter {
+    /// If not empty only files that match one of these are counted, directories are always read
+    include: GlobSet,
+    exclude: GlobSet,
+}
+
+impl ScanFilter {
+    /// Fails if one of the patterns isn't a valid glob
+    pub fn new(include: &[String], exclude: &[String]) -> Result<ScanFilter, Error> {
+        return Ok(ScanFilter {
+            include: ScanFilter::build(include)?,
+            exclude: ScanFilter::build(exclude)?,
+        });
+    }
+
+    fn build(patterns: &[String]) -> Result<GlobSet, Error> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            let glob = Glob::new(pattern).map_err(|err| Error::new(ErrorKind::InvalidInput, err.to_string()))?;
+            builder.add(glob);
+        }
+        return builder.build().map_err(|err| Error::new(ErrorKind::InvalidInput, err.to_string()));
+    }
+}
+
+impl ScanFilter {
+    /// Returns true if the entry shouldn't be read or counted
+    pub fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        if ScanFilter::matches(&self.exclude, path) {
+            return true;
+        }
+        return !is_dir && !self.include.is_empty() && !ScanFilter::matches(&self.include, path);
+    }
+
+    fn matches(set: &GlobSet, path: &Path) -> bool {
+        return set.is_match(path) || path.file_name().map(|name| set.is_match(name)).unwrap_or(false);
+    }
+}
@@ -0,0 +1,49 @@
+//! Settings for a scan
+
+use std::thread;
+use crate::scan_filter::ScanFilter;
+
+/// How a scan reads a directory, `new` gives the defaults
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    /// Number of worker threads used to read directories, always at least 1
+    pub threads: usize,
+    /// Don't read directories on other filesystems than the one the scan started on, has no effect on Windows
+    pub one_file_system: bool,
+    /// What isn't read or counted
+    pub filter: ScanFilter,
+    /// Read `.gitignore` and `.ignore` files and mark what they match as ignored
+    pub use_ignore_files: bool,
+    /// Entries deeper than this are folded into one entry per directory, their sizes are still counted
+    pub max_depth: Option<usize>,
+    /// Entries smaller than this are folded into one entry per directory, their sizes are still counted
+    pub min_size: u64,
+    /// Count what symlinks point to instead of the links themselves, directories are only read once
+    /// however many paths lead to them. Every file is then checked for being counted twice so small
+    /// files are folded at the end of the scan rather than while reading. Directories aren't followed on Windows.
+    pub follow_symlinks: bool,
+}
+
+impl ScanOptions {
+    /// One thread per CPU, staying on one filesystem and counting everything
+    pub fn new() -> ScanOptions {
+        let threads = thread::available_parallelism()
+            .map(|count| count.get())
+            .unwrap_or(1);
+        return ScanOptions {
+            threads,
+            one_file_system: true,
+            filter: ScanFilter::default(),
+            use_ignore_files: false,
+            max_depth: None,
+            min_size: 0,
+            follow_symlinks: false,
+        };
+    }
+}
+
+impl Default for ScanOptions {
+    fn default() -> ScanOptions {
+        return ScanOptions::new();
+    }
+}
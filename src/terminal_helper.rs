@@ -1,6 +1,9 @@
-use crossterm::{TerminalInput, TerminalCursor, Crossterm, Terminal, ClearType, RawScreen, KeyEvent, InputEvent, ObjectStyle, Attribute};
+use crossterm::{TerminalCursor, Crossterm, Terminal, ClearType, KeyEvent, InputEvent};
 use std::io::{stdout, Write};
-use std::cmp::{max, min};
+use std::cmp::min;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
 use crate::terminal_helper::BoxSymbols::{*};
 
 pub struct ListItem {
@@ -8,140 +11,286 @@ pub struct ListItem {
     pub selectable: bool,
 }
 
-pub struct TerminalHelper {
+/// The result of showing a list, `Key` is returned when one of the extra keys is pressed
+/// along with the index of the item under the cursor
+pub enum ListAction {
+    Selected(usize),
+    Key(char, usize),
+    /// Nothing was pressed before the timeout
+    Timeout(usize),
+    Cancelled,
+}
+
+/// Where the UI is drawn and where keys come from
+pub trait Backend {
+    /// Width and height in characters
+    fn size(&self) -> (u16, u16);
+    fn goto(&self, x: u16, y: u16);
+    /// Writes `text` at the cursor, which is left at the end of it
+    fn print(&self, text: &str);
+    fn flush(&self);
+    /// Blanks the whole screen
+    fn clear(&self);
+    fn set_cursor_visible(&self, visible: bool);
+    /// Waits for the next key, returns None if `timeout` is set and passes first
+    fn read_key(&self, timeout: Option<Duration>) -> Option<KeyEvent>;
+}
+
+/// The real terminal
+pub struct CrosstermBackend {
     terminal: Terminal,
     cursor: TerminalCursor,
-    input: TerminalInput,
+    /// Input is read on its own thread so that waiting for a key can time out
+    input_events: Receiver<InputEvent>,
 }
 
-impl TerminalHelper {
-    pub fn new() -> TerminalHelper {
+impl CrosstermBackend {
+    pub fn new() -> CrosstermBackend {
         let crossterm = Crossterm::new();
-        return TerminalHelper {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut stdin = Crossterm::new().input().read_sync();
+            loop {
+                match stdin.next() {
+                    Some(event) => {
+                        if sender.send(event).is_err() {
+                            return;
+                        }
+                    }
+                    None => thread::sleep(Duration::from_millis(10)),
+                }
+            }
+        });
+        return CrosstermBackend {
             terminal: crossterm.terminal(),
             cursor: crossterm.cursor(),
-            input: crossterm.input(),
+            input_events: receiver,
+        };
+    }
+}
+
+impl Backend for CrosstermBackend {
+    fn size(&self) -> (u16, u16) {
+        return self.terminal.terminal_size();
+    }
+
+    fn goto(&self, x: u16, y: u16) {
+        self.cursor.goto(x, y).unwrap();
+    }
+
+    fn print(&self, text: &str) {
+        print!("{}", text);
+    }
+
+    fn flush(&self) {
+        stdout().flush().unwrap();
+    }
+
+    fn clear(&self) {
+        self.terminal.clear(ClearType::All).unwrap();
+    }
+
+    fn set_cursor_visible(&self, visible: bool) {
+        if visible {
+            self.cursor.show().unwrap();
+        } else {
+            self.cursor.hide().unwrap();
+        }
+    }
+
+    fn read_key(&self, timeout: Option<Duration>) -> Option<KeyEvent> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            let event = match deadline {
+                None => self.input_events.recv().unwrap(),
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    match self.input_events.recv_timeout(remaining) {
+                        Ok(event) => event,
+                        Err(_) => return None,
+                    }
+                }
+            };
+            if let InputEvent::Keyboard(key_event) = event {
+                return Some(key_event);
+            }
+        }
+    }
+}
+
+pub struct TerminalHelper {
+    backend: Box<dyn Backend>,
+}
+
+impl TerminalHelper {
+    pub fn new() -> TerminalHelper {
+        return TerminalHelper::with_backend(Box::new(CrosstermBackend::new()));
+    }
+
+    pub fn with_backend(backend: Box<dyn Backend>) -> TerminalHelper {
+        return TerminalHelper {
+            backend,
         };
     }
 }
 
 impl TerminalHelper {
     pub fn setup(&self) {
-        self.cursor.hide().unwrap();
-        self.terminal.clear(ClearType::All).unwrap();
-        stdout().flush().unwrap();
+        self.backend.set_cursor_visible(false);
+        self.backend.clear();
+        self.backend.flush();
     }
 
     pub fn teardown(&self) {
-        self.cursor.show().unwrap();
+        self.backend.set_cursor_visible(true);
     }
 
     pub fn clear_screen(&self) {
-        self.cursor.goto(0, 0).unwrap();
-        let (w, h) = self.terminal.terminal_size();
+        self.backend.goto(0, 0);
+        let (w, h) = self.backend.size();
         for _ in 0..=h {
-            print!("{:1$}", " ", w as usize);
+            self.backend.print(&format!("{:1$}", " ", w as usize));
         }
-        self.cursor.goto(0, 0).unwrap();
-        stdout().flush().unwrap();
+        self.backend.goto(0, 0);
+        self.backend.flush();
     }
 
+    /// Shows `title` in a box in the middle of the screen, it's cut if it's wider than the terminal
     pub fn show_dialog<S: Into<String>>(&self, title: S) {
         self.clear_screen();
 
-        let message = title.into();
+        let (term_width, term_height) = self.backend.size();
+        let mut message = title.into();
+        let max_width = (term_width as usize).saturating_sub(5);
+        if message.chars().count() > max_width {
+            message = format!("{}…", message.chars().take(max_width.saturating_sub(1)).collect::<String>());
+        }
         let width = (message.chars().count() + 3) as u16;
-        let (term_width, term_height) = self.terminal.terminal_size();
         let x = (term_width / 2) - (width / 2);
         let y = (term_height / 2) - 3;
 
         self.draw_box(x, y, width, 4, None);
 
-        self.cursor.goto(x + 2, y + 2).unwrap();
-        print!("{}", message);
+        self.backend.goto(x + 2, y + 2);
+        self.backend.print(&message);
 
-        stdout().flush().unwrap();
+        self.backend.flush();
     }
 
-    pub fn draw_progress<S: Into<String>>(&self, title: S, progress: usize) {
-        let (term_width, term_height) = self.terminal.terminal_size();
+    /// Shows `message` until Enter or Esc is pressed
+    pub fn show_message<S: Into<String>>(&self, message: S) {
+        self.show_dialog(message);
+        self.wait_for_key(vec![KeyEvent::Char('\n'), KeyEvent::Esc], None);
+    }
+
+    /// Asks `question` and returns true if y is pressed, n and Esc return false
+    pub fn confirm<S: Into<String>>(&self, question: S) -> bool {
+        self.show_dialog(format!("{} (y/n)", question.into()));
+        let answer = self.wait_for_key(vec![KeyEvent::Char('y'), KeyEvent::Char('Y'), KeyEvent::Char('n'), KeyEvent::Char('N'), KeyEvent::Esc], None);
+        return matches!(answer, Some(KeyEvent::Char('y')) | Some(KeyEvent::Char('Y')));
+    }
+
+    /// Draws a progress bar with `lines` of text below it, the lines are cut to fit in the box
+    pub fn draw_progress<S: Into<String>>(&self, title: S, progress: usize, lines: &[String]) {
+        let (term_width, term_height) = self.backend.size();
 
         let percent = progress as f64 / 100_f64;
         let num_of_blocks = (term_width - 6) as f64 * percent;
 
         let width = term_width - 4;
+        let height = if lines.is_empty() { 4 } else { 5 + lines.len() as u16 };
         let x = 2;
-        let y = (term_height / 2) - 3;
+        let y = (term_height / 2).saturating_sub(height / 2 + 1);
 
-        self.draw_box(x, y, width, 4, Some(title.into()));
+        self.draw_box(x, y, width, height, Some(title.into()));
 
         for i in 1..(num_of_blocks as i64) {
-            self.cursor.goto(x + 2 + i as u16, y + 2).unwrap();
-            BoxSymbols::print_os_symbol(ProgressBlock);
+            self.backend.goto(x + 2 + i as u16, y + 2);
+            self.backend.print(&BoxSymbols::symbol_for_os(ProgressBlock));
         }
 
-        stdout().flush().unwrap();
+        let text_w = width as usize - 4;
+        for (i, line) in lines.iter().enumerate() {
+            self.backend.goto(x + 2, y + 4 + i as u16);
+            let text = if line.chars().count() > text_w {
+                let start = line.chars().count() - (text_w - 1);
+                format!("…{}", line.chars().skip(start).collect::<String>())
+            } else {
+                line.clone()
+            };
+            self.backend.print(&format!("{:1$}", text, text_w));
+        }
+
+        self.backend.flush();
     }
 
     fn draw_box(&self, x: u16, y: u16, w: u16, h: u16, message: Option<String>) {
-        self.cursor.goto(x, y).unwrap();
-        BoxSymbols::print_os_symbol(TopLeft);
-        self.cursor.goto(x, y + h).unwrap();
-        BoxSymbols::print_os_symbol(BottomLeft);
-        self.cursor.goto(x + w, y).unwrap();
-        BoxSymbols::print_os_symbol(TopRight);
-        self.cursor.goto(x + w, y + h).unwrap();
-        BoxSymbols::print_os_symbol(BottomRight);
+        self.backend.goto(x, y);
+        self.backend.print(&BoxSymbols::symbol_for_os(TopLeft));
+        self.backend.goto(x, y + h);
+        self.backend.print(&BoxSymbols::symbol_for_os(BottomLeft));
+        self.backend.goto(x + w, y);
+        self.backend.print(&BoxSymbols::symbol_for_os(TopRight));
+        self.backend.goto(x + w, y + h);
+        self.backend.print(&BoxSymbols::symbol_for_os(BottomRight));
         for i in 1..h {
-            self.cursor.goto(x, y + i).unwrap();
-            BoxSymbols::print_os_symbol(Vertical);
-            self.cursor.goto(x + w, y + i).unwrap();
-            BoxSymbols::print_os_symbol(Vertical);
+            self.backend.goto(x, y + i);
+            self.backend.print(&BoxSymbols::symbol_for_os(Vertical));
+            self.backend.goto(x + w, y + i);
+            self.backend.print(&BoxSymbols::symbol_for_os(Vertical));
         }
         for i in 1..(w) {
-            self.cursor.goto(x + i, y).unwrap();
-            BoxSymbols::print_os_symbol(Horizontal);
-            self.cursor.goto(x + i, y + h).unwrap();
-            BoxSymbols::print_os_symbol(Horizontal);
+            self.backend.goto(x + i, y);
+            self.backend.print(&BoxSymbols::symbol_for_os(Horizontal));
+            self.backend.goto(x + i, y + h);
+            self.backend.print(&BoxSymbols::symbol_for_os(Horizontal));
         }
 
         if let Some(title) = message {
-            let (term_width, _) = self.terminal.terminal_size();
+            let (term_width, _) = self.backend.size();
             let title_x = term_width / 2 - title.chars().count() as u16 / 2;
-            self.cursor.goto(title_x, y).unwrap();
-            print!("{} {} {}", BoxSymbols::symbol_for_os(TitlePrefix), title, BoxSymbols::symbol_for_os(TitleSuffix));
+            self.backend.goto(title_x, y);
+            self.backend.print(&format!("{} {} {}", BoxSymbols::symbol_for_os(TitlePrefix), title, BoxSymbols::symbol_for_os(TitleSuffix)));
         }
     }
 
+    /// The longest line a list can show before it is cut, lines this long fill the terminal's width
+    pub fn list_text_width(&self) -> usize {
+        let (term_width, _) = self.backend.size();
+        //the box is 2 narrower than the terminal so its right edge stays on screen, and has room for the cursor
+        return (term_width as usize).saturating_sub(6);
+    }
+
     pub fn show_list<S: Into<String>>(&self, title: S, list: Vec<ListItem>) -> Option<usize> {
-        let mut cursor_idx = 0_usize;
+        match self.show_list_with_keys(title, list, 0, &[], None) {
+            ListAction::Selected(idx) => return Some(idx),
+            _ => return None,
+        }
+    }
 
-        let box_x;
-        let box_y;
-        let box_w;
-        let box_h;
-        let text_w;
-        let text_h;
+    /// Shows a list with the cursor starting on `cursor_idx`, pressing any of `keys` closes the list
+    /// as does waiting longer than `timeout` if set
+    pub fn show_list_with_keys<S: Into<String>>(&self, title: S, list: Vec<ListItem>, cursor_idx: usize, keys: &[char], timeout: Option<Duration>) -> ListAction {
+        let mut cursor_idx = min(cursor_idx, list.len() - 1);
 
-        let (term_width, term_height) = self.terminal.terminal_size();
+        let (term_width, term_height) = self.backend.size();
 
         let longest_line = list.iter()
-            .max_by(|lhs, rhs| lhs.text.chars().count().cmp(&rhs.text.chars().count()))
-            .unwrap()
-            .text
-            .len();
-
-        box_w = min(term_width, longest_line as u16 + 4);
-        box_h = min(term_height, list.len() as u16 + 1);
-        box_x = max(0, term_width / 2 - box_w / 2);
-        box_y = max(0, term_height / 2 - box_h / 2);
-        text_w = box_w as usize - 4;
-        text_h = box_h - 1;
+            .map(|item| item.text.chars().count())
+            .max()
+            .unwrap();
+
+        let box_w = min(term_width, longest_line as u16 + 4);
+        let box_h = min(term_height, list.len() as u16 + 1);
+        let box_x = term_width / 2 - box_w / 2;
+        let box_y = term_height / 2 - box_h / 2;
+        let text_w = box_w as usize - 4;
+        let text_h = box_h - 1;
 
         let text_y = box_y + 1;
         let col_text = box_x + 2;
 
-        let mut list_start_idx = 0;
+        let mut list_start_idx = min(cursor_idx, list.len() - text_h as usize);
 
         self.clear_screen();
 
@@ -150,15 +299,13 @@ impl TerminalHelper {
         let list_text: Vec<String> = list.iter()
             .enumerate()
             .map(|(idx, item)| {
-                self.cursor.goto(col_text, text_y + idx as u16).unwrap();
-                let mut name;
-                if item.text.chars().count() > text_w {
-                    let (truncated, _) = item.text.split_at(text_w - 1);
-                    name = truncated.to_string();
-                    name.push_str("…");
+                self.backend.goto(col_text, text_y + idx as u16);
+                let name = if item.text.chars().count() > text_w {
+                    let truncated: String = item.text.chars().take(text_w - 1).collect();
+                    format!("{}…", truncated)
                 } else {
-                    name = item.text.clone();
-                }
+                    item.text.clone()
+                };
                 format!("{:1$}", name, text_w)
             })
             .collect();
@@ -166,72 +313,69 @@ impl TerminalHelper {
 
         loop {
             for i in 0..text_h as usize {
-                self.cursor.goto(col_text, text_y + i as u16).unwrap();
+                self.backend.goto(col_text, text_y + i as u16);
                 if cursor_idx == i + list_start_idx {
-                    print!("{} {}", BoxSymbols::symbol_for_os(ListCursor), list_text[i + list_start_idx]);
+                    self.backend.print(&format!("{} {}", BoxSymbols::symbol_for_os(ListCursor), list_text[i + list_start_idx]));
                 } else {
-                    print!("  {}", list_text[i + list_start_idx]);
+                    self.backend.print(&format!("  {}", list_text[i + list_start_idx]));
                 }
             }
-            stdout().flush().unwrap();
-            match self.wait_for_key(vec![KeyEvent::Esc, KeyEvent::Up, KeyEvent::Down, KeyEvent::Char('\n')]) {
+            self.backend.flush();
+            let mut allowed_keys = vec![KeyEvent::Esc, KeyEvent::Up, KeyEvent::Down, KeyEvent::Char('\n')];
+            allowed_keys.extend(keys.iter().map(|key| KeyEvent::Char(*key)));
+            let key = match self.wait_for_key(allowed_keys, timeout) {
+                Some(key) => key,
+                None => return ListAction::Timeout(cursor_idx),
+            };
+            match key {
                 KeyEvent::Esc => {
-                    return None;
+                    return ListAction::Cancelled;
                 }
-                KeyEvent::Up => {
-                    if cursor_idx > 0 {
-                        cursor_idx -= 1;
-                        if list_start_idx > 0 {
-                            list_start_idx -= 1;
-                        }
-                    }
+                KeyEvent::Up if cursor_idx > 0 => {
+                    cursor_idx -= 1;
+                    list_start_idx = list_start_idx.saturating_sub(1);
                 }
-                KeyEvent::Down => {
-                    if cursor_idx < list.len() - 1 {
-                        cursor_idx += 1;
-                        if list_start_idx < list.len() - text_h as usize {
-                            list_start_idx += 1;
-                        }
+                KeyEvent::Down if cursor_idx < list.len() - 1 => {
+                    cursor_idx += 1;
+                    if list_start_idx < list.len() - text_h as usize {
+                        list_start_idx += 1;
                     }
                 }
-                KeyEvent::Char(c) => {
-                    if c == '\n' {
-                        if list[cursor_idx].selectable {
-                            return Some(cursor_idx);
-                        }
-                    }
+                KeyEvent::Char('\n') if list[cursor_idx].selectable => {
+                    return ListAction::Selected(cursor_idx);
+                }
+                KeyEvent::Char(key) if key != '\n' => {
+                    return ListAction::Key(key, cursor_idx);
                 }
                 _ => {}
             }
         }
     }
 
+    #[allow(dead_code)]
     pub fn wait_for_input(&self) {
-        let mut stdin = self.input.read_sync();
-        loop {
-            if let Some(_) = stdin.next() {
-                return;
-            }
-        }
+        self.backend.read_key(None);
     }
 
-    fn wait_for_key(&self, allowed_keys: Vec<KeyEvent>) -> KeyEvent {
-        let mut stdin = self.input.read_sync();
+    /// Returns None if `timeout` is set and passes before one of `allowed_keys` is pressed
+    pub fn wait_for_key(&self, allowed_keys: Vec<KeyEvent>, timeout: Option<Duration>) -> Option<KeyEvent> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
         loop {
-            if let Some(event) = stdin.next() {
-                match event {
-                    InputEvent::Keyboard(key_event) => {
-                        if allowed_keys.contains(&key_event) {
-                            return key_event;
-                        }
-                    }
-                    _ => {}
-                }
+            let remaining = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            let key_event = self.backend.read_key(remaining)?;
+            if allowed_keys.contains(&key_event) {
+                return Some(key_event);
             }
         }
     }
 }
 
+/// A bar `width` characters wide filled in proportion to `fraction`, which is between 0 and 1
+pub fn bar(fraction: f64, width: usize) -> String {
+    let blocks = ((fraction * width as f64).round() as usize).min(width);
+    return format!("{}{}", BoxSymbols::symbol_for_os(ProgressBlock).repeat(blocks), " ".repeat(width - blocks));
+}
+
 enum BoxSymbols {
     TopLeft,
     TopRight,
@@ -283,8 +427,45 @@ impl BoxSymbols {
             return symbol.fancy().to_string();
         }
     }
+}
 
-    pub fn print_os_symbol(symbol: BoxSymbols) {
-        print!("{}", BoxSymbols::symbol_for_os(symbol));
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_terminal::{assert_screen, FakeTerminal};
+
+    #[test]
+    fn cursor_is_hidden_until_teardown() {
+        let terminal = FakeTerminal::new(40, 10, vec![]);
+        let terminal_helper = TerminalHelper::with_backend(Box::new(terminal.clone()));
+
+        terminal_helper.setup();
+        assert!(!terminal.is_cursor_visible());
+        terminal_helper.teardown();
+        assert!(terminal.is_cursor_visible());
+    }
+
+    #[test]
+    fn dialog() {
+        let terminal = FakeTerminal::new(40, 10, vec![]);
+        let terminal_helper = TerminalHelper::with_backend(Box::new(terminal.clone()));
+
+        terminal_helper.show_dialog("Gathering system info");
+
+        assert_screen("dialog", &terminal.text());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn list_times_out() {
+        let terminal = FakeTerminal::new(40, 10, vec![KeyEvent::Down]);
+        let terminal_helper = TerminalHelper::with_backend(Box::new(terminal.clone()));
+        let items = vec![
+            ListItem { text: "one".to_string(), selectable: true },
+            ListItem { text: "two".to_string(), selectable: true },
+        ];
+
+        let action = terminal_helper.show_list_with_keys("List", items, 0, &[], Some(Duration::from_secs(1)));
+
+        assert!(matches!(action, ListAction::Timeout(1)));
+    }
+}
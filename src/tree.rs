@@ -0,0 +1,626 @@
+//! The scanned tree
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::ser::{SerializeSeq, SerializeStruct};
+use crate::inspector::{DiskItem, SizeMode};
+use crate::scan_error::ScanError;
+
+/// Index of a node in a `Tree`
+pub type NodeId = u32;
+
+/// The root is always the first node
+pub const ROOT: NodeId = 0;
+
+/// Strings that are stored once and referred to by index, most names (`.git`, `index.js`, etc) appear many times
+#[derive(Debug, Clone, Default)]
+struct Names {
+    names: Vec<Arc<str>>,
+    ids: HashMap<Arc<str>, u32>,
+}
+
+impl Names {
+    fn intern(&mut self, name: &str) -> u32 {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+        let id = self.names.len() as u32;
+        let name: Arc<str> = Arc::from(name);
+        self.names.push(name.clone());
+        self.ids.insert(name, id);
+        return id;
+    }
+
+    fn get(&self, id: u32) -> &str {
+        return &self.names[id as usize];
+    }
+}
+
+const IS_DIR: u16 = 1;
+const IS_SYMLINK: u16 = 1 << 1;
+const IS_EXTRA_LINK: u16 = 1 << 2;
+const IS_MOUNT_POINT: u16 = 1 << 3;
+const IS_EXCLUDED: u16 = 1 << 4;
+const IS_IGNORED: u16 = 1 << 5;
+const IS_PENDING: u16 = 1 << 6;
+const BAD_FILE: u16 = 1 << 7;
+const READ_ERROR: u16 = 1 << 8;
+
+/// Stored in `Node::modified` when the modification time isn't known
+const NO_TIME: u64 = u64::MAX;
+
+/// The fields of a `DiskItem` that most entries need, there can be tens of millions of these so the flags
+/// are packed together and the ignored sizes are kept in `Tree::ignored_sizes`
+#[derive(Debug, Clone)]
+struct Node {
+    name: u32,
+    /// Not used for the root
+    parent: NodeId,
+    /// The children are `child_ids[children_start..children_start + children_len]`
+    children_start: u32,
+    children_len: u32,
+    aggregate_count: u32,
+    flags: u16,
+    size: u64,
+    allocated_size: u64,
+    item_count: u64,
+    modified: u64,
+}
+
+impl Node {
+    fn new(name: u32, parent: NodeId) -> Node {
+        return Node {
+            name,
+            parent,
+            children_start: 0,
+            children_len: 0,
+            aggregate_count: 0,
+            flags: 0,
+            size: 0,
+            allocated_size: 0,
+            item_count: 0,
+            modified: NO_TIME,
+        };
+    }
+}
+
+impl Node {
+    fn set_item(&mut self, item: &DiskItem) {
+        let flags = [
+            (item.is_dir, IS_DIR),
+            (item.is_symlink, IS_SYMLINK),
+            (item.is_extra_link, IS_EXTRA_LINK),
+            (item.is_mount_point, IS_MOUNT_POINT),
+            (item.is_excluded, IS_EXCLUDED),
+            (item.is_ignored, IS_IGNORED),
+            (item.is_pending, IS_PENDING),
+            (item.bad_file, BAD_FILE),
+            (item.read_error, READ_ERROR),
+        ];
+        self.flags = flags.iter().filter(|(set, _)| *set).fold(0, |flags, (_, flag)| flags | flag);
+        self.aggregate_count = item.aggregate_count.min(u32::MAX as usize) as u32;
+        self.size = item.size;
+        self.allocated_size = item.allocated_size;
+        self.item_count = item.item_count;
+        self.modified = item.modified.unwrap_or(NO_TIME);
+    }
+
+    fn has(&self, flag: u16) -> bool {
+        return self.flags & flag != 0;
+    }
+}
+
+/// A file tree stored as one list of nodes, nodes refer to their parent and children by index and
+/// only store their own name so paths are built when needed.
+/// The root's name is its full path.
+#[derive(Debug, Clone)]
+pub struct Tree {
+    nodes: Vec<Node>,
+    child_ids: Vec<NodeId>,
+    names: Names,
+    /// Ignored size and allocated size, only set for entries that have some
+    ignored_sizes: HashMap<NodeId, (u64, u64)>,
+    /// Device and inode of files with more than one hard link, only a few files have these so they aren't stored in every node
+    hard_links: HashMap<NodeId, (u64, u64)>,
+    /// Ids of directories in a running scan, only set in snapshots
+    scan_ids: HashMap<NodeId, usize>,
+    /// Where symlinks point
+    link_targets: HashMap<NodeId, PathBuf>,
+    /// What couldn't be read when the tree was scanned, these aren't exported
+    errors: Vec<ScanError>,
+}
+
+impl Tree {
+    /// A tree with just `item` as the root, named `root_path`
+    pub fn new(root_path: &Path, item: DiskItem) -> Tree {
+        let mut tree = Tree {
+            nodes: vec![],
+            child_ids: vec![],
+            names: Names::default(),
+            ignored_sizes: HashMap::new(),
+            hard_links: HashMap::new(),
+            scan_ids: HashMap::new(),
+            link_targets: HashMap::new(),
+            errors: vec![],
+        };
+        let name = tree.names.intern(&root_path.to_string_lossy());
+        tree.nodes.push(Node::new(name, ROOT));
+        tree.set_item(ROOT, item);
+        return tree;
+    }
+
+    /// A tree with just an empty directory for `root_path`
+    pub fn new_root(root_path: &Path) -> Tree {
+        return Tree::new(root_path, DiskItem::new_dir());
+    }
+}
+
+impl Tree {
+    /// The sizes and flags of the node
+    pub fn item(&self, id: NodeId) -> DiskItem {
+        let node = &self.nodes[id as usize];
+        let (ignored_size, ignored_allocated_size) = self.ignored_sizes.get(&id).cloned().unwrap_or((0, 0));
+        return DiskItem {
+            size: node.size,
+            allocated_size: node.allocated_size,
+            ignored_size,
+            ignored_allocated_size,
+            aggregate_count: node.aggregate_count as usize,
+            item_count: node.item_count,
+            modified: if node.modified == NO_TIME { None } else { Some(node.modified) },
+            is_dir: node.has(IS_DIR),
+            is_symlink: node.has(IS_SYMLINK),
+            is_extra_link: node.has(IS_EXTRA_LINK),
+            is_mount_point: node.has(IS_MOUNT_POINT),
+            is_excluded: node.has(IS_EXCLUDED),
+            is_ignored: node.has(IS_IGNORED),
+            is_pending: node.has(IS_PENDING),
+            bad_file: node.has(BAD_FILE),
+            read_error: node.has(READ_ERROR),
+        };
+    }
+
+    /// Replaces the sizes and flags of the node, its parents' totals aren't changed
+    pub fn set_item(&mut self, id: NodeId, item: DiskItem) {
+        self.nodes[id as usize].set_item(&item);
+        if item.ignored_size > 0 || item.ignored_allocated_size > 0 {
+            self.ignored_sizes.insert(id, (item.ignored_size, item.ignored_allocated_size));
+        } else {
+            self.ignored_sizes.remove(&id);
+        }
+    }
+
+    /// The full path for the root, otherwise the file name
+    pub fn name(&self, id: NodeId) -> &str {
+        return self.names.get(self.nodes[id as usize].name);
+    }
+
+    /// The name for the node, see `name`
+    pub fn set_name(&mut self, id: NodeId, name: &str) {
+        self.nodes[id as usize].name = self.names.intern(name);
+    }
+
+    /// Built from the names of the node and its parents
+    pub fn path(&self, id: NodeId) -> PathBuf {
+        let mut names = vec![];
+        let mut current = Some(id);
+        while let Some(id) = current {
+            names.push(self.name(id));
+            current = self.parent(id);
+        }
+        return names.iter().rev().collect();
+    }
+
+    /// None for the root
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        if id == ROOT {
+            return None;
+        }
+        return Some(self.nodes[id as usize].parent);
+    }
+
+    /// In the order they were added until `update_totals` sorts them
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        let node = &self.nodes[id as usize];
+        let start = node.children_start as usize;
+        return &self.child_ids[start..start + node.children_len as usize];
+    }
+
+    /// The child of `id` called `name`
+    pub fn find_child(&self, id: NodeId, name: &str) -> Option<NodeId> {
+        return self.children(id).iter().find(|child| self.name(**child) == name).cloned();
+    }
+
+    /// Finds the node for `path` by its names below the root, None if it isn't in the tree or was folded
+    pub fn find_path(&self, path: &Path) -> Option<NodeId> {
+        let relative = path.strip_prefix(self.name(ROOT)).ok()?;
+        let mut id = ROOT;
+        for name in relative.iter() {
+            id = self.find_child(id, &name.to_string_lossy())?;
+        }
+        return Some(id);
+    }
+
+    /// Adds the children of `parent`, which mustn't have any yet, and returns their ids
+    pub fn add_children(&mut self, parent: NodeId, children: Vec<(&str, DiskItem)>) -> Range<NodeId> {
+        let first = self.nodes.len() as NodeId;
+        let start = self.child_ids.len() as u32;
+        for (name, item) in children {
+            let name = self.names.intern(name);
+            let id = self.nodes.len() as NodeId;
+            self.nodes.push(Node::new(name, parent));
+            self.set_item(id, item);
+            self.child_ids.push(id);
+        }
+        let last = self.nodes.len() as NodeId;
+        let parent = &mut self.nodes[parent as usize];
+        parent.children_start = start;
+        parent.children_len = last - first;
+        return first..last;
+    }
+
+    /// Replaces the children of `parent` with `kept`, which have to be some of them, and a new node for `aggregate`
+    /// named after how many entries it stands for. The children that weren't kept are left in the list but can't be reached.
+    pub(crate) fn fold_children(&mut self, parent: NodeId, kept: &[NodeId], aggregate: Option<DiskItem>) {
+        let mut children = kept.to_vec();
+        if let Some(aggregate) = aggregate {
+            let id = self.nodes.len() as NodeId;
+            let name = self.names.intern(&aggregate.aggregate_name());
+            self.nodes.push(Node::new(name, parent));
+            self.set_item(id, aggregate);
+            children.push(id);
+        }
+        let node = &mut self.nodes[parent as usize];
+        assert!(children.len() <= node.children_len as usize, "folding can't add children");
+        node.children_len = children.len() as u32;
+        let start = node.children_start as usize;
+        self.child_ids[start..start + children.len()].copy_from_slice(&children);
+    }
+
+    /// Device and inode if the file has other hard links
+    pub fn hard_link(&self, id: NodeId) -> Option<(u64, u64)> {
+        return self.hard_links.get(&id).cloned();
+    }
+
+    /// Records the device and inode of a file that has other hard links
+    pub fn set_hard_link(&mut self, id: NodeId, file_id: (u64, u64)) {
+        self.hard_links.insert(id, file_id);
+    }
+
+    /// For nodes that are being folded
+    pub(crate) fn take_hard_link(&mut self, id: NodeId) -> Option<(u64, u64)> {
+        return self.hard_links.remove(&id);
+    }
+
+    /// The directory's id in the walker the snapshot was taken from
+    pub(crate) fn scan_id(&self, id: NodeId) -> Option<usize> {
+        return self.scan_ids.get(&id).cloned();
+    }
+
+    pub(crate) fn set_scan_id(&mut self, id: NodeId, scan_id: usize) {
+        self.scan_ids.insert(id, scan_id);
+    }
+
+    /// Where a symlink points, None if it couldn't be read
+    pub fn link_target(&self, id: NodeId) -> Option<&Path> {
+        return self.link_targets.get(&id).map(|target| target.as_path());
+    }
+
+    /// Records where a symlink points
+    pub fn set_link_target(&mut self, id: NodeId, target: PathBuf) {
+        self.link_targets.insert(id, target);
+    }
+
+    /// Sorted by path
+    pub fn errors(&self) -> &[ScanError] {
+        return &self.errors;
+    }
+
+    /// Replaces the errors, which should be sorted by path
+    pub fn set_errors(&mut self, errors: Vec<ScanError>) {
+        self.errors = errors;
+    }
+
+    /// The apparent or allocated size of the node
+    pub fn size_for(&self, id: NodeId, mode: SizeMode) -> u64 {
+        return self.item(id).size_for(mode);
+    }
+
+    /// Adds the sizes and item counts of the children to the node and sorts them, directories first and then by name
+    pub fn update_totals(&mut self, id: NodeId) {
+        let mut totals = DiskItem::default();
+        for child in self.children(id) {
+            let child = self.item(*child);
+            if !child.is_extra_link {
+                totals.size += child.size;
+                totals.allocated_size += child.allocated_size;
+                totals.ignored_size += child.ignored_size;
+                totals.ignored_allocated_size += child.ignored_allocated_size;
+            }
+        }
+        let mut item = self.item(id);
+        item.size += totals.size;
+        item.allocated_size += totals.allocated_size;
+        item.ignored_size += totals.ignored_size;
+        item.ignored_allocated_size += totals.ignored_allocated_size;
+        self.set_item(id, item);
+        self.count_items(id);
+
+        let mut children = self.children(id).to_vec();
+        children.sort_by(|lhs, rhs| Tree::compare(&self.item(*lhs), self.name(*lhs), &self.item(*rhs), self.name(*rhs)));
+        let start = self.nodes[id as usize].children_start as usize;
+        self.child_ids[start..start + children.len()].copy_from_slice(&children);
+    }
+
+    /// Takes the node and everything under it out of the tree, such as after it was deleted, and takes
+    /// its sizes and item count off its parents, which imported trees may not add up to. Its nodes are left in the list but can't be reached.
+    /// A hard linked file that was counted here and has other links left in the tree is counted at one of those instead.
+    pub fn remove(&mut self, id: NodeId) {
+        let parent = self.parent(id).expect("the root can't be removed");
+        let removed = self.item(id);
+        if !removed.is_extra_link {
+            self.change_ancestor_sizes(id, &removed, false);
+        }
+        let mut ancestor = Some(parent);
+        while let Some(ancestor_id) = ancestor {
+            let node = &mut self.nodes[ancestor_id as usize];
+            node.item_count = node.item_count.saturating_sub(removed.aggregate_count.max(1) as u64 + removed.item_count);
+            ancestor = self.parent(ancestor_id);
+        }
+
+        let node = &self.nodes[parent as usize];
+        let start = node.children_start as usize;
+        let end = start + node.children_len as usize;
+        let idx = self.child_ids[start..end].iter().position(|child| *child == id).unwrap();
+        self.child_ids[start + idx..end].rotate_left(1);
+        self.nodes[parent as usize].children_len -= 1;
+
+        let mut counted_links = vec![];
+        let mut removed_ids = vec![id];
+        while let Some(removed_id) = removed_ids.pop() {
+            if let Some(file_id) = self.hard_links.remove(&removed_id) {
+                if !self.item(removed_id).is_extra_link {
+                    counted_links.push(file_id);
+                }
+            }
+            removed_ids.extend_from_slice(self.children(removed_id));
+        }
+        for file_id in counted_links {
+            self.count_remaining_link(file_id);
+        }
+
+        let path = self.path(id);
+        self.errors.retain(|error| !error.path.starts_with(&path));
+    }
+
+    /// Takes everything under the directory out of the tree and marks it and its parents as pending,
+    /// for when only some of it could be deleted so what's left isn't known until it's read again
+    pub fn mark_unread(&mut self, id: NodeId) {
+        for child in self.children(id).to_vec() {
+            self.remove(child);
+        }
+        let mut node = Some(id);
+        while let Some(node_id) = node {
+            let mut item = self.item(node_id);
+            item.is_pending = true;
+            self.set_item(node_id, item);
+            node = self.parent(node_id);
+        }
+    }
+
+    /// Counts the first, by path, of the links to `file_id` left in the tree if none of them are counted
+    fn count_remaining_link(&mut self, file_id: (u64, u64)) {
+        let links: Vec<NodeId> = self.hard_links.iter()
+            .filter(|(id, link)| **link == file_id && self.is_reachable(**id))
+            .map(|(id, _)| *id)
+            .collect();
+        if links.iter().any(|id| !self.item(*id).is_extra_link) {
+            return;
+        }
+        if let Some(link) = links.into_iter().min_by_key(|id| self.path(*id)) {
+            let mut item = self.item(link);
+            item.is_extra_link = false;
+            self.set_item(link, item.clone());
+            self.change_ancestor_sizes(link, &item, true);
+        }
+    }
+
+    /// Adds the sizes of `item` to, or takes them off, the parents of `id`
+    fn change_ancestor_sizes(&mut self, id: NodeId, item: &DiskItem, add: bool) {
+        let change = |total: u64, size: u64| if add { total + size } else { total.saturating_sub(size) };
+        let mut ancestor = self.parent(id);
+        while let Some(ancestor_id) = ancestor {
+            let mut ancestor_item = self.item(ancestor_id);
+            ancestor_item.size = change(ancestor_item.size, item.size);
+            ancestor_item.allocated_size = change(ancestor_item.allocated_size, item.allocated_size);
+            ancestor_item.ignored_size = change(ancestor_item.ignored_size, item.ignored_size);
+            ancestor_item.ignored_allocated_size = change(ancestor_item.ignored_allocated_size, item.ignored_allocated_size);
+            self.set_item(ancestor_id, ancestor_item);
+            ancestor = self.parent(ancestor_id);
+        }
+    }
+
+    /// False for nodes that have been removed or folded, which are still in the list
+    fn is_reachable(&self, id: NodeId) -> bool {
+        let mut id = id;
+        while let Some(parent) = self.parent(id) {
+            if !self.children(parent).contains(&id) {
+                return false;
+            }
+            id = parent;
+        }
+        return true;
+    }
+
+    /// Sets the item count of the node from those of its children, which have to have been counted already
+    fn count_items(&mut self, id: NodeId) {
+        let item_count = self.children(id).iter()
+            .map(|child| self.item(*child))
+            .map(|child| child.aggregate_count.max(1) as u64 + child.item_count)
+            .sum();
+        self.nodes[id as usize].item_count = item_count;
+    }
+
+    /// Directories first and then by name
+    pub fn compare(lhs: &DiskItem, lhs_name: &str, rhs: &DiskItem, rhs_name: &str) -> Ordering {
+        return lhs.is_dir.cmp(&rhs.is_dir).reverse().then(lhs_name.to_lowercase().cmp(&rhs_name.to_lowercase()));
+    }
+}
+
+/// A node and everything under it, written in the same shape as the tree so it can be read back
+struct NodeRef<'a> {
+    tree: &'a Tree,
+    id: NodeId,
+    path: PathBuf,
+}
+
+impl<'a> Serialize for NodeRef<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let item = self.tree.item(self.id);
+        let name = self.path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        //the files directly in a directory, without its subdirectories
+        let (files_size, files_allocated_size) = self.tree.children(self.id)
+            .iter()
+            .map(|child| self.tree.item(*child))
+            .filter(|child| !child.is_dir && !child.is_symlink && !child.is_extra_link)
+            .fold((0, 0), |(size, allocated_size), child| (size + child.size, allocated_size + child.allocated_size));
+        let mut state = serializer.serialize_struct("DiskItem", 20)?;
+        state.serialize_field("path", &self.path)?;
+        state.serialize_field("name", &name)?;
+        state.serialize_field("size", &item.size)?;
+        state.serialize_field("allocated_size", &item.allocated_size)?;
+        state.serialize_field("files_size", &files_size)?;
+        state.serialize_field("files_allocated_size", &files_allocated_size)?;
+        state.serialize_field("is_dir", &item.is_dir)?;
+        state.serialize_field("is_symlink", &item.is_symlink)?;
+        state.serialize_field("link_target", &self.tree.link_target(self.id))?;
+        state.serialize_field("is_extra_link", &item.is_extra_link)?;
+        state.serialize_field("is_mount_point", &item.is_mount_point)?;
+        state.serialize_field("is_excluded", &item.is_excluded)?;
+        state.serialize_field("is_ignored", &item.is_ignored)?;
+        state.serialize_field("aggregate_count", &item.aggregate_count)?;
+        state.serialize_field("ignored_size", &item.ignored_size)?;
+        state.serialize_field("ignored_allocated_size", &item.ignored_allocated_size)?;
+        state.serialize_field("bad_file", &item.bad_file)?;
+        state.serialize_field("read_error", &item.read_error)?;
+        state.serialize_field("modified", &item.modified)?;
+        state.serialize_field("children", &Children { tree: self.tree, id: self.id, path: &self.path })?;
+        return state.end();
+    }
+}
+
+struct Children<'a> {
+    tree: &'a Tree,
+    id: NodeId,
+    path: &'a Path,
+}
+
+impl<'a> Serialize for Children<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let children = self.tree.children(self.id);
+        let mut seq = serializer.serialize_seq(Some(children.len()))?;
+        for child in children {
+            seq.serialize_element(&NodeRef { tree: self.tree, id: *child, path: self.path.join(self.tree.name(*child)) })?;
+        }
+        return seq.end();
+    }
+}
+
+/// Written as nested entries starting at the root
+impl Serialize for Tree {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        return NodeRef { tree: self, id: ROOT, path: self.path(ROOT) }.serialize(serializer);
+    }
+}
+
+/// An entry as written by `Serialize`, `files_size` and `files_allocated_size` are worked out from the children so aren't read
+#[derive(Deserialize)]
+struct JsonNode {
+    path: PathBuf,
+    #[serde(default)]
+    name: Option<String>,
+    size: u64,
+    #[serde(default)]
+    allocated_size: u64,
+    is_dir: bool,
+    is_symlink: bool,
+    #[serde(default)]
+    link_target: Option<PathBuf>,
+    #[serde(default)]
+    is_extra_link: bool,
+    #[serde(default)]
+    is_mount_point: bool,
+    #[serde(default)]
+    is_excluded: bool,
+    #[serde(default)]
+    is_ignored: bool,
+    #[serde(default)]
+    aggregate_count: usize,
+    #[serde(default)]
+    ignored_size: u64,
+    #[serde(default)]
+    ignored_allocated_size: u64,
+    #[serde(default)]
+    bad_file: bool,
+    #[serde(default)]
+    read_error: bool,
+    #[serde(default)]
+    modified: Option<u64>,
+    children: Vec<JsonNode>,
+}
+
+impl JsonNode {
+    fn item(&self) -> DiskItem {
+        return DiskItem {
+            size: self.size,
+            allocated_size: self.allocated_size,
+            ignored_size: self.ignored_size,
+            ignored_allocated_size: self.ignored_allocated_size,
+            aggregate_count: self.aggregate_count,
+            item_count: 0,
+            modified: self.modified,
+            is_dir: self.is_dir,
+            is_symlink: self.is_symlink,
+            is_extra_link: self.is_extra_link,
+            is_mount_point: self.is_mount_point,
+            is_excluded: self.is_excluded,
+            is_ignored: self.is_ignored,
+            is_pending: false,
+            bad_file: self.bad_file,
+            read_error: self.read_error,
+        };
+    }
+
+    fn name(&self) -> String {
+        return match &self.name {
+            Some(name) => name.clone(),
+            None => self.path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default(),
+        };
+    }
+
+    fn add_children(self, tree: &mut Tree, id: NodeId) {
+        let names: Vec<String> = self.children.iter().map(|child| child.name()).collect();
+        let items = self.children.iter().zip(&names).map(|(child, name)| (name.as_str(), child.item())).collect();
+        let ids = tree.add_children(id, items);
+        for (mut child, child_id) in self.children.into_iter().zip(ids) {
+            if let Some(link_target) = child.link_target.take() {
+                tree.set_link_target(child_id, link_target);
+            }
+            child.add_children(tree, child_id);
+        }
+        tree.count_items(id);
+    }
+}
+
+impl<'de> Deserialize<'de> for Tree {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Tree, D::Error> {
+        let root = JsonNode::deserialize(deserializer)?;
+        let mut tree = Tree::new(&root.path, root.item());
+        root.add_children(&mut tree, ROOT);
+        return Ok(tree);
+    }
+}
@@ -1,5 +1,161 @@
-use crate::human_readable_bytes;
-use crate::terminal_helper::{TerminalHelper, ListItem};
+use disk_inspector::format::human_readable_bytes;
+use disk_inspector::inspector::{IgnoreFilter, ScanProgress, SizeMode, SortMode};
+use disk_inspector::scan_error::ScanError;
+use disk_inspector::tree::{NodeId, Tree};
+use std::time::Duration;
+use crate::terminal_helper::{bar, TerminalHelper, ListItem, ListAction};
+
+const KEY_TOGGLE_SIZE_MODE: char = 'a';
+const KEY_TOGGLE_IGNORE_FILTER: char = 'i';
+const KEY_SHOW_ERRORS: char = 'e';
+const KEY_TOGGLE_SORT_MODE: char = 's';
+const KEY_DELETE: char = 'd';
+
+/// Names are cut to this before any other column is left out
+const MIN_NAME_WIDTH: usize = 20;
+const BAR_WIDTH: usize = 10;
+/// The marker and the size
+const SIZE_COLUMNS_WIDTH: usize = 11;
+/// "100.0%"
+const PERCENT_WIDTH: usize = 6;
+
+struct DirListItem {
+    name: String,
+    size: u64,
+    /// Share of the directory's size, None for `..`
+    percent: Option<f64>,
+    /// Share of the largest entry's size, which the bar shows
+    bar_fraction: f64,
+    /// Only set for directories that were read
+    item_count: Option<u64>,
+    is_dir: bool,
+    is_extra_link: bool,
+    is_mount_point: bool,
+    is_excluded: bool,
+    is_ignored: bool,
+    /// Stands for several smaller entries
+    is_aggregate: bool,
+    is_pending: bool,
+    /// It, or some of what is in it, couldn't be read
+    has_error: bool,
+}
+
+impl DirListItem {
+    fn marker(&self) -> &str {
+        if self.is_mount_point {
+            return "M";
+        }
+        if self.is_excluded {
+            return "X";
+        }
+        if self.has_error {
+            return "!";
+        }
+        if self.is_pending {
+            return "…";
+        }
+        if self.is_aggregate {
+            return "+";
+        }
+        if self.is_ignored {
+            return "I";
+        }
+        if self.is_dir {
+            return "D";
+        }
+        if self.is_extra_link {
+            return "H";
+        }
+        return "";
+    }
+}
+
+/// Which columns fit in a row next to the name, the bar is left out first and then the item count and the percentage
+struct DirColumns {
+    name_width: usize,
+    show_percent: bool,
+    show_bar: bool,
+    /// 0 if the item counts aren't shown
+    item_count_width: usize,
+}
+
+impl DirColumns {
+    fn fit(width: usize, item_count_width: usize) -> DirColumns {
+        let mut columns = DirColumns {
+            name_width: width.saturating_sub(SIZE_COLUMNS_WIDTH).max(MIN_NAME_WIDTH),
+            show_percent: false,
+            show_bar: false,
+            item_count_width: 0,
+        };
+        if columns.name_width > MIN_NAME_WIDTH + PERCENT_WIDTH {
+            columns.show_percent = true;
+            columns.name_width -= PERCENT_WIDTH + 1;
+        }
+        if item_count_width > 0 && columns.name_width > MIN_NAME_WIDTH + item_count_width {
+            columns.item_count_width = item_count_width;
+            columns.name_width -= item_count_width + 1;
+        }
+        if columns.name_width >= MIN_NAME_WIDTH + BAR_WIDTH + 3 {
+            columns.show_bar = true;
+            columns.name_width -= BAR_WIDTH + 3;
+        }
+        return columns;
+    }
+}
+
+impl DirColumns {
+    fn row(&self, item: &DirListItem) -> String {
+        let name = if item.name.chars().count() > self.name_width {
+            let truncated: String = item.name.chars().take(self.name_width - 1).collect();
+            format!("{}…", truncated)
+        } else {
+            item.name.clone()
+        };
+        let mut row = format!("{:<3$} {:<1} {:>8}", name, item.marker(), human_readable_bytes(item.size as f64), self.name_width);
+        if self.show_percent {
+            let percent = item.percent.map(|percent| format!("{:.1}%", percent)).unwrap_or_default();
+            row.push_str(&format!(" {:>1$}", percent, PERCENT_WIDTH));
+        }
+        if self.show_bar {
+            match item.percent {
+                Some(_) => row.push_str(&format!(" [{}]", bar(item.bar_fraction, BAR_WIDTH))),
+                None => row.push_str(&" ".repeat(BAR_WIDTH + 3)),
+            }
+        }
+        if self.item_count_width > 0 {
+            row.push_str(&format!(" {:>1$}", item.item_count.map(item_count_text).unwrap_or_default(), self.item_count_width));
+        }
+        return row;
+    }
+}
+
+pub enum DirAction {
+    Open(NodeId),
+    /// Go to the parent directory
+    Up,
+    /// Switch between apparent and allocated sizes, the index of the item under the cursor is included
+    ToggleSizeMode(usize),
+    /// Switch between showing everything, only what isn't ignored and only what is ignored
+    ToggleIgnoreFilter(usize),
+    /// Switch to the next way of sorting the entries, the index of the item under the cursor is included
+    ToggleSortMode(usize),
+    /// Delete the entry, the index of the item under the cursor is included
+    Delete(NodeId, usize),
+    /// Show what couldn't be read, the index of the item under the cursor is included
+    ShowErrors(usize),
+    /// Nothing was pressed before the refresh interval passed, the index of the item under the cursor is included
+    Refresh(usize),
+    Exit,
+}
+
+/// What to do after Esc is pressed while the disk is being read
+pub enum CancelAction {
+    KeepReading,
+    /// Stop reading and browse what has been read so far
+    Browse,
+    /// Stop reading and go back to the disk list
+    PickDisk,
+}
 
 pub fn show_disk_list(terminal_helper: &TerminalHelper, disk_info_list: Vec<(String, u64, u64)>) -> Option<usize> {
     terminal_helper.clear_screen();
@@ -7,7 +163,7 @@ pub fn show_disk_list(terminal_helper: &TerminalHelper, disk_info_list: Vec<(Str
     let items = disk_info_list.iter()
         .map(|disk| {
             let remaining = disk.2 - disk.1;
-            let percent = 1_f64 - (disk.1 as f64 / disk.2 as f64);
+            let percent = (1_f64 - (disk.1 as f64 / disk.2 as f64)) * 100_f64;
             let remaining_text = format!("{}/{}", human_readable_bytes(remaining as f64), human_readable_bytes(disk.2 as f64));
             let text = format!("{:<20} {:>14} ({:.2}%)", disk.0, remaining_text, percent);
 
@@ -21,31 +177,335 @@ pub fn show_disk_list(terminal_helper: &TerminalHelper, disk_info_list: Vec<(Str
     return terminal_helper.show_list("Select a hard drive", items);
 }
 
-pub fn draw_dir_items(terminal_helper: &TerminalHelper, title: String, show_go_up: bool, mut contents: Vec<(String, u64, bool)>) -> Option<String> {
-    
-    if show_go_up {
-        contents.insert(0, ("..".to_string(), 0, true));
+pub fn show_cancel_menu(terminal_helper: &TerminalHelper) -> CancelAction {
+    let items = vec![
+        ListItem { text: "Keep reading".to_string(), selectable: true },
+        ListItem { text: "Stop and browse what has been read".to_string(), selectable: true },
+        ListItem { text: "Stop and pick another disk".to_string(), selectable: true },
+    ];
+
+    match terminal_helper.show_list("Stop reading?", items) {
+        Some(1) => return CancelAction::Browse,
+        Some(2) => return CancelAction::PickDisk,
+        _ => return CancelAction::KeepReading,
+    }
+}
+
+/// Draws the progress bar with the counts, speed, time left and the directory being read
+pub fn draw_scan_progress(terminal_helper: &TerminalHelper, progress: &ScanProgress) {
+    let time_left = match progress.time_left {
+        Some(time_left) => format!("about {} left", human_readable_duration(time_left)),
+        None => "working out time left".to_string(),
+    };
+    let lines = vec![
+        format!("{} files, {} directories, {}", progress.files, progress.dirs, human_readable_bytes(progress.bytes as f64)),
+        format!("{:.0} items/s, {}", progress.items_per_second, time_left),
+        progress.current_path.to_string_lossy().to_string(),
+    ];
+    terminal_helper.draw_progress(format!("Reading files {}%", progress.percentage), progress.percentage, &lines);
+}
+
+fn human_readable_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        return format!("{}s", secs);
+    }
+    if secs < 60 * 60 {
+        return format!("{}m {}s", secs / 60, secs % 60);
     }
+    return format!("{}h {}m", secs / (60 * 60), (secs / 60) % 60);
+}
 
-    let items = contents.iter()
-        .map(|item| {
-            let dir = if item.2 { "D" } else { "" };
-            let original_name = item.0.clone();
-            let mut name;
-            if original_name.len() > 75 {
-                let (truncated_name, _) = original_name.split_at(74);
-                name = truncated_name.to_string();
-                name.push('…');
-            } else {
-                name = item.0.clone();
+/// How the entries of a directory are shown
+pub struct DirListOptions {
+    /// Adds a `..` entry at the top
+    pub show_go_up: bool,
+    pub size_mode: SizeMode,
+    pub ignore_filter: IgnoreFilter,
+    pub sort_mode: SortMode,
+    /// If set `DirAction::Refresh` is returned when nothing is pressed for this long
+    pub refresh_interval: Option<Duration>,
+}
+
+/// Shows the children of `dir` in `tree`
+pub fn draw_dir_items(terminal_helper: &TerminalHelper, title: String, tree: &Tree, dir: NodeId, options: &DirListOptions, cursor_idx: usize) -> DirAction {
+    let mut children: Vec<NodeId> = tree.children(dir)
+        .iter()
+        .filter(|child| tree.item(**child).is_shown(options.ignore_filter))
+        .cloned()
+        .collect();
+    options.sort_mode.sort(tree, &mut children, options.size_mode, options.ignore_filter);
+
+    let dir_size = tree.item(dir).filtered_size(options.size_mode, options.ignore_filter);
+    let largest_size = children.iter()
+        .map(|child| tree.item(*child).filtered_size(options.size_mode, options.ignore_filter))
+        .max()
+        .unwrap_or(0);
+    let mut contents: Vec<DirListItem> = children.iter()
+        .map(|child| {
+            let item = tree.item(*child);
+            let size = item.filtered_size(options.size_mode, options.ignore_filter);
+            DirListItem {
+                name: match tree.link_target(*child) {
+                    Some(target) => format!("{} -> {}", tree.name(*child), target.display()),
+                    None => tree.name(*child).to_string(),
+                },
+                size,
+                percent: Some(if dir_size > 0 { size as f64 * 100_f64 / dir_size as f64 } else { 0_f64 }),
+                bar_fraction: if largest_size > 0 { size as f64 / largest_size as f64 } else { 0_f64 },
+                item_count: if item.is_dir && !item.is_mount_point && !item.is_excluded { Some(item.item_count) } else { None },
+                is_dir: item.is_dir,
+                is_extra_link: item.is_extra_link,
+                is_mount_point: item.is_mount_point,
+                is_excluded: item.is_excluded,
+                is_ignored: item.is_ignored,
+                is_aggregate: item.aggregate_count > 0,
+                is_pending: item.is_pending,
+                has_error: item.read_error || item.bad_file,
             }
-            let text = format!("{:<75} {:<1} {:>8}", name, dir, human_readable_bytes(item.1 as f64));
+        })
+        .collect();
+
+    if options.show_go_up {
+        contents.insert(0, DirListItem { name: "..".to_string(), size: 0, percent: None, bar_fraction: 0_f64, item_count: None, is_dir: true, is_extra_link: false, is_mount_point: false, is_excluded: false, is_ignored: false, is_aggregate: false, is_pending: false, has_error: false });
+    }
+
+    let item_count_width = contents.iter()
+        .filter_map(|item| item.item_count)
+        .map(|count| item_count_text(count).chars().count())
+        .max()
+        .unwrap_or(0);
+    let columns = DirColumns::fit(terminal_helper.list_text_width(), item_count_width);
+    let mut items = contents.iter()
+        .map(|item| {
             ListItem {
-                text,
-                selectable: item.2
+                text: columns.row(item),
+                selectable: item.is_dir && !item.is_mount_point && !item.is_excluded
+            }
+        })
+        .collect::<Vec<ListItem>>();
+
+    if items.is_empty() {
+        items.push(ListItem {
+            text: "(empty)".to_string(),
+            selectable: false,
+        });
+    }
+
+    match terminal_helper.show_list_with_keys(title, items, cursor_idx, &[KEY_TOGGLE_SIZE_MODE, KEY_TOGGLE_IGNORE_FILTER, KEY_SHOW_ERRORS, KEY_TOGGLE_SORT_MODE, KEY_DELETE], options.refresh_interval) {
+        ListAction::Selected(0) if options.show_go_up => return DirAction::Up,
+        ListAction::Selected(idx) if options.show_go_up => return DirAction::Open(children[idx - 1]),
+        ListAction::Selected(idx) => return DirAction::Open(children[idx]),
+        ListAction::Key(KEY_TOGGLE_SIZE_MODE, idx) => return DirAction::ToggleSizeMode(idx),
+        ListAction::Key(KEY_TOGGLE_IGNORE_FILTER, idx) => return DirAction::ToggleIgnoreFilter(idx),
+        ListAction::Key(KEY_SHOW_ERRORS, idx) => return DirAction::ShowErrors(idx),
+        ListAction::Key(KEY_TOGGLE_SORT_MODE, idx) => return DirAction::ToggleSortMode(idx),
+        ListAction::Key(KEY_DELETE, idx) => {
+            let child_idx = if options.show_go_up { idx.checked_sub(1) } else { Some(idx) };
+            match child_idx.and_then(|child_idx| children.get(child_idx)) {
+                Some(child) => return DirAction::Delete(*child, idx),
+                //`..` or the empty list, there's nothing to delete so the list is just drawn again
+                None => return DirAction::Refresh(idx),
             }
+        }
+        ListAction::Timeout(idx) => return DirAction::Refresh(idx),
+        _ => return DirAction::Exit,
+    }
+}
+
+/// Lists what couldn't be read, returns the index of the error picked or None if Esc was pressed
+pub fn show_error_list(terminal_helper: &TerminalHelper, errors: &[ScanError]) -> Option<usize> {
+    let mut items: Vec<ListItem> = errors.iter()
+        .map(|error| ListItem {
+            text: error.to_string(),
+            selectable: true,
         })
         .collect();
 
-    return terminal_helper.show_list(title,items).and_then(|idx| Some(contents[idx].0.clone()));
-}
\ No newline at end of file
+    if items.is_empty() {
+        items.push(ListItem {
+            text: "(no errors)".to_string(),
+            selectable: false,
+        });
+    }
+
+    return terminal_helper.show_list(error_count_text(errors.len()), items);
+}
+
+/// "1 item", "2 items", etc
+fn item_count_text(count: u64) -> String {
+    let plural = if count == 1 { "" } else { "s" };
+    return format!("{} item{}", count, plural);
+}
+
+/// "1 error", "2 errors", etc
+pub fn error_count_text(count: usize) -> String {
+    let plural = if count == 1 { "" } else { "s" };
+    return format!("{} error{}", count, plural);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_terminal::{assert_screen, FakeTerminal};
+    use crossterm::KeyEvent;
+    use disk_inspector::cancel_token::CancelToken;
+    use disk_inspector::file_system::MemoryFileSystem;
+    use disk_inspector::inspector::{Inspector, LiveScan};
+    use disk_inspector::scan_options::ScanOptions;
+    use disk_inspector::tree::ROOT;
+    use std::path::PathBuf;
+    use std::sync::mpsc;
+    use std::sync::Arc;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn scan(fs: MemoryFileSystem) -> Tree {
+        let (events, _receiver) = mpsc::channel();
+        let scan = LiveScan::with_file_system(PathBuf::from("/data"), &ScanOptions::new(), Arc::new(CancelToken::new()), Arc::new(fs));
+        return Inspector::for_directory(ScanOptions::new(), events).populate_with(&scan);
+    }
+
+    fn options() -> DirListOptions {
+        return DirListOptions {
+            show_go_up: false,
+            size_mode: SizeMode::Apparent,
+            ignore_filter: IgnoreFilter::All,
+            sort_mode: SortMode::Name,
+            refresh_interval: None,
+        };
+    }
+
+    #[test]
+    fn disk_list() {
+        let terminal = FakeTerminal::new(80, 12, vec![KeyEvent::Down, KeyEvent::Char('\n')]);
+        let terminal_helper = TerminalHelper::with_backend(Box::new(terminal.clone()));
+        let disks = vec![
+            ("/".to_string(), 20 << 30, 100 << 30),
+            ("/home".to_string(), 300 << 30, 500 << 30),
+            ("/mnt/usb".to_string(), 1 << 30, 16 << 30),
+        ];
+
+        let selected = show_disk_list(&terminal_helper, disks);
+
+        assert_eq!(selected, Some(1));
+        assert_screen("disk_list", &terminal.screens()[0]);
+        assert_screen("disk_list_second", &terminal.screens()[1]);
+    }
+
+    #[test]
+    fn scan_progress() {
+        let terminal = FakeTerminal::new(80, 12, vec![]);
+        let terminal_helper = TerminalHelper::with_backend(Box::new(terminal.clone()));
+        let progress = ScanProgress {
+            percentage: 40,
+            files: 1200,
+            dirs: 80,
+            bytes: 5 << 30,
+            current_path: PathBuf::from(format!("/home/user/{}", "projects/".repeat(10))),
+            items_per_second: 640.0,
+            time_left: Some(Duration::from_secs(150)),
+        };
+
+        draw_scan_progress(&terminal_helper, &progress);
+
+        assert_screen("scan_progress", &terminal.text());
+    }
+
+    #[test]
+    fn long_names_are_cut() {
+        let mut fs = MemoryFileSystem::new();
+        fs.add_file(format!("/data/{}", "long_name_".repeat(10)), 2000);
+        fs.add_file("/data/short", 1000);
+        fs.add_symlink("/data/link", format!("/somewhere/{}", "deep/".repeat(20)));
+        let tree = scan(fs);
+        let terminal = FakeTerminal::new(100, 10, vec![KeyEvent::Esc]);
+        let terminal_helper = TerminalHelper::with_backend(Box::new(terminal.clone()));
+
+        let action = draw_dir_items(&terminal_helper, "/data".to_string(), &tree, ROOT, &options(), 0);
+
+        assert!(matches!(action, DirAction::Exit));
+        assert_screen("long_names", &terminal.text());
+    }
+
+    #[test]
+    fn long_lists_scroll() {
+        let mut fs = MemoryFileSystem::new();
+        for idx in 0..20 {
+            fs.add_file(format!("/data/file{:02}", idx), 1000 + idx);
+        }
+        let tree = scan(fs);
+        let mut keys = vec![KeyEvent::Down; 12];
+        keys.push(KeyEvent::Char('a'));
+        let terminal = FakeTerminal::new(100, 10, keys);
+        let terminal_helper = TerminalHelper::with_backend(Box::new(terminal.clone()));
+
+        let action = draw_dir_items(&terminal_helper, "/data".to_string(), &tree, ROOT, &options(), 0);
+
+        assert!(matches!(action, DirAction::ToggleSizeMode(12)));
+        let screens = terminal.screens();
+        assert_screen("scroll_top", &screens[0]);
+        assert_screen("scroll_down", &screens[12]);
+    }
+
+    #[test]
+    fn list_starts_at_the_cursor() {
+        let mut fs = MemoryFileSystem::new();
+        for idx in 0..20 {
+            fs.add_file(format!("/data/file{:02}", idx), 1000 + idx);
+        }
+        let tree = scan(fs);
+        let terminal = FakeTerminal::new(100, 10, vec![KeyEvent::Up, KeyEvent::Esc]);
+        let terminal_helper = TerminalHelper::with_backend(Box::new(terminal.clone()));
+
+        draw_dir_items(&terminal_helper, "/data".to_string(), &tree, ROOT, &options(), 15);
+
+        let screens = terminal.screens();
+        assert_screen("scroll_from_cursor", &screens[0]);
+        assert_screen("scroll_from_cursor_up", &screens[1]);
+    }
+
+    #[test]
+    fn columns_are_left_out_on_narrow_terminals() {
+        let mut fs = MemoryFileSystem::new();
+        fs.add_file("/data/photos/cat.jpg", 3000);
+        fs.add_file("/data/photos/dog.jpg", 5000);
+        fs.add_file("/data/notes_from_the_meeting_last_week.txt", 1000);
+        let tree = scan(fs);
+        let terminal = FakeTerminal::new(50, 8, vec![KeyEvent::Esc]);
+        let terminal_helper = TerminalHelper::with_backend(Box::new(terminal.clone()));
+
+        draw_dir_items(&terminal_helper, "/data".to_string(), &tree, ROOT, &options(), 0);
+
+        assert_screen("narrow_list", &terminal.text());
+    }
+
+    #[test]
+    fn sort_modes() {
+        let mut fs = MemoryFileSystem::new();
+        fs.add_file("/data/a.txt", 300);
+        fs.set_modified("/data/a.txt", UNIX_EPOCH + Duration::from_secs(1000));
+        fs.add_file("/data/b.jpg", 100);
+        fs.set_modified("/data/b.jpg", UNIX_EPOCH + Duration::from_secs(3000));
+        fs.add_file("/data/dir/x.png", 60);
+        fs.add_file("/data/dir/y.png", 60);
+        fs.set_modified("/data/dir", UNIX_EPOCH + Duration::from_secs(2000));
+        let tree = scan(fs);
+        let listed = |sort_mode| {
+            let terminal = FakeTerminal::new(100, 10, vec![KeyEvent::Esc]);
+            let terminal_helper = TerminalHelper::with_backend(Box::new(terminal.clone()));
+            let options = DirListOptions { sort_mode, ..options() };
+            draw_dir_items(&terminal_helper, "/data".to_string(), &tree, ROOT, &options, 0);
+            return terminal.text().lines()
+                .filter_map(|line| line.split('┃').nth(1))
+                .map(|row| row.trim_start_matches([' ', '▶']).split_whitespace().next().unwrap().to_string())
+                .collect::<Vec<String>>();
+        };
+
+        assert_eq!(listed(SortMode::SizeDescending), ["a.txt", "dir", "b.jpg"]);
+        assert_eq!(listed(SortMode::SizeAscending), ["b.jpg", "dir", "a.txt"]);
+        assert_eq!(listed(SortMode::Name), ["dir", "a.txt", "b.jpg"]);
+        assert_eq!(listed(SortMode::ItemCount), ["dir", "a.txt", "b.jpg"]);
+        assert_eq!(listed(SortMode::Modified), ["b.jpg", "dir", "a.txt"]);
+        assert_eq!(listed(SortMode::Extension), ["dir", "b.jpg", "a.txt"]);
+    }
+}
@@ -0,0 +1,730 @@
+//! Reads a directory tree on several threads
+
+use std::collections::{HashMap, HashSet};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+use crate::cancel_token::CancelToken;
+use crate::file_system::{FileInfo, FileSystem, RealFileSystem};
+use crate::ignore_rules::IgnoreRules;
+use crate::inspector::{DiskItem, Status};
+use crate::scan_error::ScanError;
+use crate::scan_filter::ScanFilter;
+use crate::scan_options::ScanOptions;
+use crate::tree::{NodeId, Tree, ROOT};
+use crate::work_queue::WorkQueue;
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(50);
+const ROOT_ID: usize = 0;
+
+struct DirJob {
+    id: usize,
+    path: PathBuf,
+    /// The root is 0
+    depth: usize,
+    /// Everything in an ignored directory is ignored
+    ignored: bool,
+    /// Not set unless ignore files are being used
+    ignore_rules: Option<Arc<IgnoreRules>>,
+}
+
+/// How far a walk has got, directories found includes the ones that have been read
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct WalkProgress {
+    /// Allocated size of everything read so far
+    pub(crate) bytes_counted: u64,
+    pub(crate) files_read: usize,
+    pub(crate) dirs_read: usize,
+    pub(crate) dirs_found: usize,
+    /// The directory most recently started
+    pub(crate) current_path: PathBuf,
+}
+
+/// An entry read from a directory, only kept until the directory has been read and it is added to the tree
+struct Entry {
+    name: String,
+    item: DiskItem,
+    /// Device and inode if the file has more than one hard link, or of every file when following symlinks
+    file_id: Option<(u64, u64)>,
+    /// Whether the file has other hard links, if not `file_id` is only used to find other paths to it through symlinks
+    hard_linked: bool,
+    /// Where a symlink points
+    link_target: Option<PathBuf>,
+    /// Set for directories that are going to be read
+    dir: Option<DirJob>,
+    /// Set for symlinks to directories when following symlinks
+    link: Option<DirJob>,
+}
+
+impl Entry {
+    fn new(name: String, item: DiskItem) -> Entry {
+        return Entry { name, item, file_id: None, hard_linked: false, link_target: None, dir: None, link: None };
+    }
+}
+
+/// A symlink to a directory found while following symlinks, these are followed once everything found
+/// so far has been read so that directories are read from their own path if they are in the scan
+struct PendingLink {
+    node: NodeId,
+    /// The id is set once it has been decided to follow the link
+    job: DirJob,
+}
+
+/// The entries read from a directory
+type Listing = Vec<Entry>;
+
+/// What is known about a directory while the walk is running
+struct DirState {
+    path: PathBuf,
+    parent: Option<usize>,
+    node: NodeId,
+    /// Set once the directory has been read and its entries added to the tree
+    is_read: bool,
+    /// Number of subdirectories that haven't been completely read
+    pending: usize,
+    /// Sizes of the entries read so far, including the subdirectories that have been completely read
+    size: u64,
+    allocated_size: u64,
+    ignored_size: u64,
+    ignored_allocated_size: u64,
+    /// Number of entries read so far, counted the same way as `DiskItem::item_count`
+    item_count: u64,
+    /// Set once the directory and everything under it has been read
+    complete: bool,
+    /// Set if the directory, or some of its entries, couldn't be read
+    read_error: bool,
+}
+
+impl DirState {
+    fn new(path: PathBuf, parent: Option<usize>, node: NodeId) -> DirState {
+        return DirState {
+            path,
+            parent,
+            node,
+            is_read: false,
+            pending: 0,
+            size: 0,
+            allocated_size: 0,
+            ignored_size: 0,
+            ignored_allocated_size: 0,
+            item_count: 0,
+            complete: false,
+            read_error: false,
+        };
+    }
+}
+
+/// What the threads share while the walk is running
+struct WalkState {
+    /// The entries of each directory are added as soon as it has been read, they are folded and
+    /// their hard links are counted once everything has been read
+    tree: Tree,
+    dirs: HashMap<usize, DirState>,
+    /// The job ids of the nodes of directories that are going to be read
+    dir_ids: HashMap<NodeId, usize>,
+    /// Device and inode of files without other hard links, only kept when following symlinks.
+    /// They aren't kept in the tree as it only records hard links
+    file_ids: HashMap<NodeId, (u64, u64)>,
+    pending_links: Vec<PendingLink>,
+}
+
+/// Used when the tree is assembled to mark every path to a file after the first one as an extra link
+struct SeenFiles {
+    counted: HashSet<(u64, u64)>,
+    /// Files reached through symlinks that aren't hard linked, from `WalkState`
+    file_ids: HashMap<NodeId, (u64, u64)>,
+}
+
+/// Reads a directory tree using a pool of threads that take directories from a shared queue.
+/// The entries of each directory are added to a `Tree` as soon as it has been read and once every directory
+/// has been read the tree is folded and its hard links are counted in sorted order, so the result is the
+/// same no matter how many threads are used.
+///
+/// While the walk is running `snapshot` can be used from other threads to see what has been read so far.
+/// If `cancel_token` is cancelled the walk stops after the directories currently being read and
+/// returns what has been read, directories that weren't completely read are marked as pending.
+///
+/// Anything that can't be read is recorded as a `ScanError` and the walk carries on.
+pub(crate) struct Walker {
+    root: PathBuf,
+    fs: Arc<dyn FileSystem>,
+    threads: usize,
+    root_device: Option<u64>,
+    filter: ScanFilter,
+    use_ignore_files: bool,
+    max_depth: Option<usize>,
+    min_size: u64,
+    follow_symlinks: bool,
+    /// Device and inode of every directory queued, only used when following symlinks
+    visited_dirs: Mutex<HashSet<(u64, u64)>>,
+    queue: WorkQueue<DirJob>,
+    state: Mutex<WalkState>,
+    next_id: AtomicUsize,
+    files_read: AtomicUsize,
+    dirs_read: AtomicUsize,
+    bytes_counted: AtomicU64,
+    current_path: Mutex<PathBuf>,
+    errors: Mutex<Vec<ScanError>>,
+    cancel_token: Arc<CancelToken>,
+}
+
+impl Walker {
+    pub(crate) fn new(root: PathBuf, options: &ScanOptions, cancel_token: Arc<CancelToken>) -> Walker {
+        return Walker::with_file_system(root, options, cancel_token, Arc::new(RealFileSystem));
+    }
+
+    /// Reads from `fs` instead of the disk
+    pub(crate) fn with_file_system(root: PathBuf, options: &ScanOptions, cancel_token: Arc<CancelToken>, fs: Arc<dyn FileSystem>) -> Walker {
+        let root_info = fs.metadata(&root).ok();
+        let root_device = if options.one_file_system {
+            root_info.as_ref().and_then(FileInfo::device)
+        } else {
+            None
+        };
+        let mut dirs = HashMap::new();
+        dirs.insert(ROOT_ID, DirState::new(root.clone(), None, ROOT));
+        let mut dir_ids = HashMap::new();
+        dir_ids.insert(ROOT, ROOT_ID);
+        //the root's own size is counted like that of every other directory
+        let root_item = root_info.as_ref()
+            .map(|info| DiskItem { is_dir: true, ..DiskItem::new(info) })
+            .unwrap_or_else(DiskItem::new_dir);
+        let bytes_counted = root_item.allocated_size;
+        let state = WalkState { tree: Tree::new(&root, root_item), dirs, dir_ids, file_ids: HashMap::new(), pending_links: vec![] };
+        let visited_dirs = root_info.and_then(|info| info.file_id).into_iter().collect();
+        return Walker {
+            root,
+            fs,
+            threads: options.threads.max(1),
+            root_device,
+            filter: options.filter.clone(),
+            use_ignore_files: options.use_ignore_files,
+            max_depth: options.max_depth,
+            min_size: options.min_size,
+            follow_symlinks: options.follow_symlinks,
+            visited_dirs: Mutex::new(visited_dirs),
+            queue: WorkQueue::new(),
+            state: Mutex::new(state),
+            next_id: AtomicUsize::new(ROOT_ID + 1),
+            files_read: AtomicUsize::new(0),
+            dirs_read: AtomicUsize::new(0),
+            bytes_counted: AtomicU64::new(bytes_counted),
+            current_path: Mutex::new(PathBuf::new()),
+            errors: Mutex::new(vec![]),
+            cancel_token,
+        };
+    }
+}
+
+impl Walker {
+    /// Reads everything under the root, `progress` is called on the calling thread whenever
+    /// more has been read. Directories entered and finished and errors are sent to `events`
+    /// from the threads reading them. The errors in the tree are sorted by path.
+    ///
+    /// When following symlinks the directories they point to are read in rounds, after everything found in
+    /// the round before has been read, so which path a directory is read from doesn't depend on timing.
+    pub(crate) fn walk(&self, events: &Sender<Status>, mut progress: impl FnMut(WalkProgress)) -> Tree {
+        let ignore_rules = if self.use_ignore_files { Some(Arc::new(IgnoreRules::default())) } else { None };
+        self.queue.push(DirJob { id: ROOT_ID, path: self.root.clone(), depth: 0, ignored: false, ignore_rules });
+
+        loop {
+            thread::scope(|scope| {
+                for _ in 0..self.threads {
+                    scope.spawn(|| self.work(events));
+                }
+                let mut last_progress = self.progress();
+                while !self.queue.wait_until_finished(PROGRESS_INTERVAL) {
+                    let current_progress = self.progress();
+                    if current_progress != last_progress {
+                        progress(current_progress.clone());
+                        last_progress = current_progress;
+                    }
+                }
+            });
+            if self.cancel_token.is_cancelled() || !self.follow_pending_links() {
+                break;
+            }
+        }
+
+        progress(self.progress());
+
+        let mut state = self.state.lock().unwrap();
+        let mut tree = mem::replace(&mut state.tree, Tree::new_root(&self.root));
+        let dirs = mem::take(&mut state.dirs);
+        let dir_ids = mem::take(&mut state.dir_ids);
+        let file_ids = mem::take(&mut state.file_ids);
+        drop(state);
+        self.assemble(&mut tree, ROOT, 0, &dirs, &dir_ids, &mut SeenFiles { counted: HashSet::new(), file_ids });
+        let mut errors = self.errors();
+        errors.sort_by(|lhs, rhs| lhs.path.cmp(&rhs.path));
+        tree.set_errors(errors);
+        return tree;
+    }
+
+    /// Returns the directory with its children as they are now, the children that are
+    /// directories have no children of their own and have their scan id set so they can be
+    /// passed to this method. Returns None once the walk has finished.
+    ///
+    /// Hard links are only found once the walk has finished so may be counted more than once.
+    pub(crate) fn snapshot(&self, id: usize) -> Option<Tree> {
+        let state = self.state.lock().unwrap();
+        let dir = state.dirs.get(&id)?;
+        let mut root_item = state.tree.item(dir.node);
+        root_item.is_pending = !dir.complete;
+        root_item.read_error = dir.read_error;
+        let mut tree = Tree::new(&dir.path, root_item);
+        tree.set_scan_id(ROOT, id);
+        if dir.is_read {
+            let walked = state.tree.children(dir.node);
+            let children = walked.iter()
+                .map(|child| {
+                    let mut item = state.tree.item(*child);
+                    if let Some(child_state) = Walker::dir_state(*child, &state.dirs, &state.dir_ids) {
+                        item.size += child_state.size;
+                        item.allocated_size += child_state.allocated_size;
+                        item.ignored_size += child_state.ignored_size;
+                        item.ignored_allocated_size += child_state.ignored_allocated_size;
+                        item.item_count += child_state.item_count;
+                        item.is_pending = !child_state.complete;
+                        item.read_error = child_state.read_error;
+                    }
+                    (state.tree.name(*child), item)
+                })
+                .collect();
+            let ids = tree.add_children(ROOT, children);
+            for (walked_child, child) in walked.iter().zip(ids) {
+                if let Some(dir_id) = state.dir_ids.get(walked_child) {
+                    tree.set_scan_id(child, *dir_id);
+                }
+                if let Some(link_target) = state.tree.link_target(*walked_child) {
+                    tree.set_link_target(child, link_target.to_path_buf());
+                }
+            }
+        }
+        tree.update_totals(ROOT);
+        return Some(tree);
+    }
+
+    /// What couldn't be read so far, in the order it was found
+    pub(crate) fn errors(&self) -> Vec<ScanError> {
+        return self.errors.lock().unwrap().clone();
+    }
+
+    pub(crate) fn error_count(&self) -> usize {
+        return self.errors.lock().unwrap().len();
+    }
+
+    pub(crate) fn root(&self) -> &Path {
+        return &self.root;
+    }
+
+    pub(crate) fn root_id(&self) -> usize {
+        return ROOT_ID;
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        return self.cancel_token.is_cancelled();
+    }
+
+    fn progress(&self) -> WalkProgress {
+        return WalkProgress {
+            bytes_counted: self.bytes_counted.load(Ordering::SeqCst),
+            files_read: self.files_read.load(Ordering::SeqCst),
+            dirs_read: self.dirs_read.load(Ordering::SeqCst),
+            dirs_found: self.next_id.load(Ordering::SeqCst),
+            current_path: self.current_path.lock().unwrap().clone(),
+        };
+    }
+
+    fn work(&self, events: &Sender<Status>) {
+        while let Some(job) = self.queue.pop() {
+            if self.cancel_token.is_cancelled() {
+                self.queue.close();
+            } else {
+                self.read_dir(job, events);
+            }
+            self.queue.done();
+        }
+    }
+
+    fn add_error(&self, error: ScanError, events: &Sender<Status>) {
+        let _ = events.send(Status::Error(error.clone()));
+        self.errors.lock().unwrap().push(error);
+    }
+
+    fn read_dir(&self, job: DirJob, events: &Sender<Status>) {
+        *self.current_path.lock().unwrap() = job.path.clone();
+        let _ = events.send(Status::DirEntered { path: job.path.clone() });
+        let ignore_rules = job.ignore_rules.as_ref().map(|rules| IgnoreRules::for_dir(rules, &job.path, self.fs.as_ref()));
+        let mut listing = vec![];
+        let mut files_read = 0;
+        //directories take up space too so are counted, otherwise the total never reaches the used space
+        let mut bytes_read = 0;
+        let mut read_error = false;
+        let dir_entries = match self.fs.read_dir(&job.path) {
+            Ok(dir_entries) => Some(dir_entries),
+            Err(err) => {
+                self.add_error(ScanError::new(job.path.clone(), "read directory", &err), events);
+                read_error = true;
+                None
+            }
+        };
+        for dir_entry in dir_entries.into_iter().flatten() {
+            let dir_entry = match dir_entry {
+                Ok(dir_entry) => dir_entry,
+                Err(err) => {
+                    self.add_error(ScanError::new(job.path.clone(), "read an entry in", &err), events);
+                    read_error = true;
+                    continue;
+                }
+            };
+            let path = job.path.join(&dir_entry.name);
+            let info = match dir_entry.info {
+                Ok(info) => Some(info),
+                //usually because it was deleted after the directory was listed
+                Err(err) => {
+                    self.add_error(ScanError::new(path.clone(), "read the metadata of", &err), events);
+                    None
+                }
+            };
+            let mut item = match &info {
+                Some(info) => DiskItem::new(info),
+                None => DiskItem { bad_file: true, ..DiskItem::default() },
+            };
+            let device = info.as_ref().and_then(FileInfo::device);
+            //every file is checked when following symlinks as a link to it may be found elsewhere
+            let file_id = match &info {
+                Some(info) if self.follow_symlinks && !item.is_dir && !item.is_symlink => info.file_id,
+                Some(info) => info.hard_link_id(),
+                None => None,
+            };
+            let link_target = if item.is_symlink { self.fs.read_link(&path).ok() } else { None };
+            let mut entry = Entry::new(dir_entry.name.to_string_lossy().to_string(), DiskItem::default());
+            entry.file_id = file_id;
+            entry.hard_linked = info.as_ref().and_then(FileInfo::hard_link_id).is_some();
+            entry.link_target = link_target;
+            item.is_ignored = job.ignored || ignore_rules.as_ref()
+                .map(|rules| rules.is_ignored(&path, item.is_dir))
+                .unwrap_or(false);
+            if self.filter.is_excluded(&path, item.is_dir) {
+                trace!("Excluding {:?}", path);
+                item.is_excluded = true;
+                item.size = 0;
+                item.allocated_size = 0;
+                entry.file_id = None;
+            } else if item.is_dir && self.is_other_filesystem(device) {
+                debug!("Skipping mount point {:?}", path);
+                item.is_mount_point = true;
+                item.size = 0;
+                item.allocated_size = 0;
+            } else if item.is_dir && !self.visit_dir(info.as_ref()) {
+                debug!("Not reading {:?}, it has already been read through a symlink", path);
+                item.is_extra_link = true;
+            } else if item.is_dir {
+                bytes_read += item.allocated_size;
+                entry.dir = Some(DirJob {
+                    id: self.next_id.fetch_add(1, Ordering::SeqCst),
+                    path,
+                    depth: job.depth + 1,
+                    ignored: item.is_ignored,
+                    ignore_rules: ignore_rules.clone(),
+                });
+            } else if item.is_symlink && self.follow_symlinks {
+                match self.fs.metadata(&path) {
+                    Ok(target) if target.is_dir => {
+                        //marked as a directory so it isn't folded before it's followed
+                        item.is_dir = true;
+                        entry.link = Some(DirJob { id: 0, path, depth: job.depth + 1, ignored: item.is_ignored, ignore_rules: ignore_rules.clone() });
+                    }
+                    Ok(target) => {
+                        files_read += 1;
+                        bytes_read += target.allocated_size;
+                        item = DiskItem { is_symlink: true, is_ignored: item.is_ignored, ..DiskItem::new(&target) };
+                        entry.file_id = target.file_id;
+                        entry.hard_linked = target.hard_link_id().is_some();
+                    }
+                    //the link is counted if what it points to doesn't exist
+                    Err(_) => files_read += 1,
+                }
+            } else {
+                files_read += 1;
+                if !item.is_symlink {
+                    bytes_read += item.allocated_size;
+                }
+            }
+            if item.is_ignored {
+                item.ignored_size = item.size;
+                item.ignored_allocated_size = item.allocated_size;
+            }
+            entry.item = item;
+            listing.push(entry);
+        }
+
+        //Folded now so that small files don't use memory until the end, hard links have to be kept
+        //until the end to find out which is counted
+        let fold_all = self.is_below_max_depth(job.depth);
+        let (small, mut listing): (Listing, Listing) = listing.into_iter()
+            .partition(|entry| entry.dir.is_none() && !entry.item.is_dir && entry.file_id.is_none() && !entry.item.is_excluded
+                && (fold_all || self.is_small(&entry.item, entry.item.size)));
+        if small.len() > 1 || (fold_all && !small.is_empty()) {
+            let mut aggregate = DiskItem::default();
+            for entry in &small {
+                aggregate.fold(&entry.item);
+            }
+            listing.push(Entry::new(aggregate.aggregate_name(), aggregate));
+        } else {
+            listing.extend(small);
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let WalkState { tree, dirs, dir_ids, file_ids, pending_links } = &mut *state;
+        let dir = dirs.get_mut(&job.id).unwrap();
+        dir.size = listing.iter().map(|entry| entry.item.size).sum();
+        dir.allocated_size = listing.iter().map(|entry| entry.item.allocated_size).sum();
+        dir.ignored_size = listing.iter().map(|entry| entry.item.ignored_size).sum();
+        dir.ignored_allocated_size = listing.iter().map(|entry| entry.item.ignored_allocated_size).sum();
+        dir.item_count = listing.iter().map(|entry| entry.item.aggregate_count.max(1) as u64).sum();
+        dir.pending = listing.iter().filter(|entry| entry.dir.is_some()).count();
+        dir.read_error = read_error;
+        dir.is_read = true;
+        let node = dir.node;
+
+        let children = listing.iter().map(|entry| (entry.name.as_str(), entry.item.clone())).collect();
+        let ids = tree.add_children(node, children);
+        let mut new_dirs = vec![];
+        for (entry, child) in listing.into_iter().zip(ids) {
+            match entry.file_id {
+                Some(file_id) if entry.hard_linked => tree.set_hard_link(child, file_id),
+                Some(file_id) => {
+                    file_ids.insert(child, file_id);
+                }
+                None => {}
+            }
+            if let Some(link_target) = entry.link_target {
+                tree.set_link_target(child, link_target);
+            }
+            if let Some(new_dir) = entry.dir {
+                dirs.insert(new_dir.id, DirState::new(new_dir.path.clone(), Some(job.id), child));
+                dir_ids.insert(child, new_dir.id);
+                new_dirs.push(new_dir);
+            }
+            if let Some(link) = entry.link {
+                pending_links.push(PendingLink { node: child, job: link });
+            }
+        }
+        Walker::complete_if_read(dirs, job.id, events);
+        drop(state);
+
+        for new_dir in new_dirs {
+            self.queue.push(new_dir);
+        }
+        self.bytes_counted.fetch_add(bytes_read, Ordering::SeqCst);
+        self.files_read.fetch_add(files_read, Ordering::SeqCst);
+        self.dirs_read.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Marks the directory as complete if it and all its subdirectories have been read,
+    /// and adds its sizes to its parent which may then be complete as well
+    fn complete_if_read(dirs: &mut HashMap<usize, DirState>, id: usize, events: &Sender<Status>) {
+        let mut id = id;
+        loop {
+            let state = dirs.get_mut(&id).unwrap();
+            if !state.is_read || state.pending > 0 {
+                return;
+            }
+            state.complete = true;
+            let _ = events.send(Status::DirFinished { path: state.path.clone(), size: state.size, allocated_size: state.allocated_size });
+            let (size, allocated_size) = (state.size, state.allocated_size);
+            let (ignored_size, ignored_allocated_size) = (state.ignored_size, state.ignored_allocated_size);
+            let item_count = state.item_count;
+            match state.parent {
+                None => return,
+                Some(parent_id) => {
+                    let parent = dirs.get_mut(&parent_id).unwrap();
+                    parent.size += size;
+                    parent.allocated_size += allocated_size;
+                    parent.ignored_size += ignored_size;
+                    parent.ignored_allocated_size += ignored_allocated_size;
+                    parent.item_count += item_count;
+                    parent.pending -= 1;
+                    id = parent_id;
+                }
+            }
+        }
+    }
+
+    /// Records that the directory is being read, returns false if it already has been because
+    /// a symlink led to it. Always true unless following symlinks.
+    fn visit_dir(&self, info: Option<&FileInfo>) -> bool {
+        if !self.follow_symlinks {
+            return true;
+        }
+        return match info.and_then(|info| info.file_id) {
+            Some(file_id) => self.visited_dirs.lock().unwrap().insert(file_id),
+            None => true,
+        };
+    }
+
+    /// Queues the directories that the symlinks found in the last round point to, in path order so the
+    /// same link is always followed. Links to directories that have already been read are marked as extra
+    /// links. Returns false if nothing was queued.
+    fn follow_pending_links(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let WalkState { tree, dirs, dir_ids, pending_links, .. } = &mut *state;
+        let mut links = mem::take(pending_links);
+        links.sort_by(|lhs, rhs| lhs.job.path.cmp(&rhs.job.path));
+        let mut jobs = vec![];
+        for mut link in links {
+            let mut item = tree.item(link.node);
+            let target = match self.fs.metadata(&link.job.path) {
+                Ok(target) => target,
+                Err(_) => {
+                    item.is_dir = false;
+                    tree.set_item(link.node, item);
+                    continue;
+                }
+            };
+            if self.is_other_filesystem(target.device()) {
+                debug!("Not following {:?} to another filesystem", link.job.path);
+                item.is_mount_point = true;
+                tree.set_item(link.node, item);
+                continue;
+            }
+            //on Windows there is no way to tell if the directory has been read so links aren't followed
+            if !target.file_id.map(|file_id| self.visited_dirs.lock().unwrap().insert(file_id)).unwrap_or(false) {
+                debug!("Not following {:?}, it leads to a directory that has already been read", link.job.path);
+                item.is_dir = false;
+                item.is_extra_link = true;
+                tree.set_item(link.node, item);
+                continue;
+            }
+            link.job.id = self.next_id.fetch_add(1, Ordering::SeqCst);
+            item = DiskItem { is_symlink: true, is_ignored: link.job.ignored, ..DiskItem::new(&target) };
+            if link.job.ignored {
+                item.ignored_size = item.size;
+                item.ignored_allocated_size = item.allocated_size;
+            }
+            tree.set_item(link.node, item);
+            dir_ids.insert(link.node, link.job.id);
+            //the directories aren't added to their parents' sizes as those have already been completed
+            dirs.insert(link.job.id, DirState::new(link.job.path.clone(), None, link.node));
+            jobs.push(link.job);
+        }
+        drop(state);
+
+        if jobs.is_empty() {
+            return false;
+        }
+        self.queue.reopen();
+        for job in jobs {
+            self.queue.push(job);
+        }
+        return true;
+    }
+
+    /// Entries in a directory at or below the max depth are folded together
+    fn is_below_max_depth(&self, depth: usize) -> bool {
+        return self.max_depth.map(|max_depth| depth >= max_depth).unwrap_or(false);
+    }
+
+    /// `size` is the total for directories
+    fn is_small(&self, disk_item: &DiskItem, size: u64) -> bool {
+        return size < self.min_size || disk_item.aggregate_count > 0;
+    }
+
+    fn is_other_filesystem(&self, device: Option<u64>) -> bool {
+        return self.root_device.is_some() && device.is_some() && device != self.root_device;
+    }
+
+    fn dir_state<'a>(node: NodeId, dirs: &'a HashMap<usize, DirState>, dir_ids: &HashMap<NodeId, usize>) -> Option<&'a DirState> {
+        return dir_ids.get(&node).and_then(|id| dirs.get(id));
+    }
+
+    /// Directories first and then by name
+    fn sorted_children(tree: &Tree, node: NodeId) -> Vec<NodeId> {
+        let mut children = tree.children(node).to_vec();
+        children.sort_by(|lhs, rhs| Tree::compare(&tree.item(*lhs), tree.name(*lhs), &tree.item(*rhs), tree.name(*rhs)));
+        return children;
+    }
+
+    /// Goes through the tree depth first in sorted order, so the first path found for a hard linked file is
+    /// always the same one and any others are marked as extra links, and adds up the sizes of the directories.
+    /// Directories that weren't read because the walk was cancelled, and their parents, are marked as pending.
+    /// Entries below the max depth or min size are folded into one entry now that their sizes are known,
+    /// the sizes of directories used for this include every path to a hard linked file.
+    fn assemble(&self, tree: &mut Tree, node: NodeId, depth: usize, dirs: &HashMap<usize, DirState>, dir_ids: &HashMap<NodeId, usize>, seen_files: &mut SeenFiles) {
+        let state = Walker::dir_state(node, dirs, dir_ids);
+        let mut item = tree.item(node);
+        item.read_error = state.map(|state| state.read_error).unwrap_or(false);
+        item.is_pending = !state.map(|state| state.is_read).unwrap_or(false);
+        tree.set_item(node, item);
+        if tree.item(node).is_pending {
+            return;
+        }
+        let children = Walker::sorted_children(tree, node);
+
+        let fold_all = self.is_below_max_depth(depth);
+        let mut folded: Vec<bool> = children.iter()
+            .map(|child| {
+                let item = tree.item(*child);
+                let size = item.size + Walker::dir_state(*child, dirs, dir_ids).map(|state| state.size).unwrap_or(0);
+                fold_all || (!item.is_excluded && !item.is_mount_point && self.is_small(&item, size))
+            })
+            .collect();
+        //A single entry is only folded if it's the only thing below the max depth
+        if folded.iter().filter(|folded| **folded).count() == 1 {
+            let idx = folded.iter().position(|folded| *folded).unwrap();
+            if tree.item(children[idx]).aggregate_count > 0 || !fold_all {
+                folded[idx] = false;
+            }
+        }
+
+        let mut kept = vec![];
+        let mut aggregate = DiskItem::default();
+        for (child, folded) in children.iter().zip(&folded) {
+            if *folded {
+                Walker::fold_node(tree, &mut aggregate, *child, dirs, dir_ids, seen_files);
+                continue;
+            }
+            kept.push(*child);
+            if dir_ids.contains_key(child) {
+                self.assemble(tree, *child, depth + 1, dirs, dir_ids, seen_files);
+            }
+            if let Some(file_id) = tree.hard_link(*child).or_else(|| seen_files.file_ids.get(child).cloned()) {
+                let mut item = tree.item(*child);
+                item.is_extra_link = !seen_files.counted.insert(file_id);
+                tree.set_item(*child, item);
+            }
+        }
+        let aggregate = if folded.contains(&true) { Some(aggregate) } else { None };
+        tree.fold_children(node, &kept, aggregate);
+
+        let mut item = tree.item(node);
+        item.is_pending = tree.children(node).iter().any(|child| tree.item(*child).is_pending);
+        tree.set_item(node, item);
+        tree.update_totals(node);
+    }
+
+    /// Folds the node, and everything under it if it's a directory, into `aggregate` in the same order `assemble` uses
+    fn fold_node(tree: &mut Tree, aggregate: &mut DiskItem, node: NodeId, dirs: &HashMap<usize, DirState>, dir_ids: &HashMap<NodeId, usize>, seen_files: &mut SeenFiles) {
+        let mut item = tree.item(node);
+        if let Some(file_id) = tree.take_hard_link(node).or_else(|| seen_files.file_ids.remove(&node)) {
+            item.is_extra_link = !seen_files.counted.insert(file_id);
+        }
+        if let Some(state) = Walker::dir_state(node, dirs, dir_ids) {
+            item.read_error = state.read_error;
+            if state.is_read {
+                for child in Walker::sorted_children(tree, node) {
+                    Walker::fold_node(tree, aggregate, child, dirs, dir_ids, seen_files);
+                }
+            } else {
+                item.is_pending = true;
+            }
+        }
+        aggregate.fold(&item);
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::VecDeque;
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+/// A queue of jobs shared between worker threads, where workers can add more jobs
+/// while processing one. The queue is finished once it is empty and no worker is busy.
+pub struct WorkQueue<T> {
+    state: Mutex<QueueState<T>>,
+    condvar: Condvar,
+}
+
+struct QueueState<T> {
+    jobs: VecDeque<T>,
+    active: usize,
+    closed: bool,
+}
+
+impl<T> WorkQueue<T> {
+    pub fn new() -> WorkQueue<T> {
+        return WorkQueue {
+            state: Mutex::new(QueueState {
+                jobs: VecDeque::new(),
+                active: 0,
+                closed: false,
+            }),
+            condvar: Condvar::new(),
+        };
+    }
+}
+
+impl<T> WorkQueue<T> {
+    pub fn push(&self, job: T) {
+        let mut state = self.state.lock().unwrap();
+        state.jobs.push_back(job);
+        self.condvar.notify_one();
+    }
+
+    /// Blocks until a job is available, returns None once the queue has finished or been closed.
+    /// Every job returned must be followed by a call to `done`.
+    pub fn pop(&self) -> Option<T> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if state.closed {
+                return None;
+            }
+            if let Some(job) = state.jobs.pop_front() {
+                state.active += 1;
+                return Some(job);
+            }
+            if state.active == 0 {
+                state.closed = true;
+                self.condvar.notify_all();
+                return None;
+            }
+            state = self.condvar.wait(state).unwrap();
+        }
+    }
+
+    pub fn done(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.active -= 1;
+        if state.active == 0 && state.jobs.is_empty() {
+            state.closed = true;
+        }
+        self.condvar.notify_all();
+    }
+
+    /// Stops all workers, any remaining jobs are dropped
+    pub fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        state.jobs.clear();
+        self.condvar.notify_all();
+    }
+
+    /// Lets a finished queue be used again, for jobs that can only be added once everything before them is done
+    pub fn reopen(&self) {
+        self.state.lock().unwrap().closed = false;
+    }
+
+    /// Returns true if the queue has finished, waiting at most `timeout` for that to happen
+    pub fn wait_until_finished(&self, timeout: Duration) -> bool {
+        let state = self.state.lock().unwrap();
+        if state.closed {
+            return true;
+        }
+        let (state, _) = self.condvar.wait_timeout(state, timeout).unwrap();
+        return state.closed;
+    }
+}
@@ -0,0 +1,624 @@
+#![allow(clippy::needless_return)]
+
+use disk_inspector::analysis::largest_at_depth;
+use disk_inspector::cancel_token::CancelToken;
+use disk_inspector::file_system::{FileSystem, MemoryFileSystem};
+use disk_inspector::inspector::{Disk, DiskItem, Inspector, LiveScan, SizeMode, Status};
+use disk_inspector::ncdu::{read_ncdu, write_ncdu, MAX_DEPTH};
+use disk_inspector::scan_filter::ScanFilter;
+use disk_inspector::scan_options::ScanOptions;
+use disk_inspector::tree::{Tree, ROOT};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+fn options() -> ScanOptions {
+    let mut options = ScanOptions::new();
+    options.threads = 4;
+    return options;
+}
+
+/// Scans /data and returns the tree and every event sent
+fn scan(fs: MemoryFileSystem, options: &ScanOptions) -> (Tree, Vec<Status>) {
+    let (events, receiver) = mpsc::channel();
+    let scan = LiveScan::with_file_system(PathBuf::from("/data"), options, Arc::new(CancelToken::new()), Arc::new(fs));
+    let tree = Inspector::for_directory(options.clone(), events).populate_with(&scan);
+    return (tree, receiver.try_iter().collect());
+}
+
+fn item(tree: &Tree, path: &str) -> DiskItem {
+    let id = tree.find_path(Path::new(path)).unwrap_or_else(|| panic!("{} isn't in the tree", path));
+    return tree.item(id);
+}
+
+fn child_names(tree: &Tree, path: &str) -> Vec<String> {
+    let id = tree.find_path(Path::new(path)).unwrap();
+    return tree.children(id).iter().map(|child| tree.name(*child).to_string()).collect();
+}
+
+fn photos() -> MemoryFileSystem {
+    let mut fs = MemoryFileSystem::new();
+    fs.add_file("/data/photos/cat.jpg", 3000);
+    fs.add_file("/data/photos/dog.jpg", 5000);
+    fs.add_file("/data/photos/2019/beach.jpg", 10_000);
+    fs.add_file("/data/notes.txt", 100);
+    fs.add_dir("/data/empty");
+    return fs;
+}
+
+#[test]
+fn directories_include_everything_under_them() {
+    let (tree, _) = scan(photos(), &options());
+
+    assert_eq!(tree.item(ROOT).size, 18_100);
+    assert_eq!(item(&tree, "/data/photos").size, 18_000);
+    assert_eq!(item(&tree, "/data/photos/2019").size, 10_000);
+    assert_eq!(item(&tree, "/data/empty").size, 0);
+    assert!(tree.errors().is_empty());
+}
+
+#[test]
+fn allocated_sizes_are_whole_blocks() {
+    let (tree, _) = scan(photos(), &options());
+
+    assert_eq!(item(&tree, "/data/notes.txt").allocated_size, 4096);
+    assert_eq!(item(&tree, "/data/photos").allocated_size, 4096 + 8192 + 12_288);
+    assert_eq!(tree.size_for(ROOT, SizeMode::Allocated), 4096 * 7);
+}
+
+#[test]
+fn directories_are_listed_first_then_by_name() {
+    let mut fs = MemoryFileSystem::new();
+    fs.add_file("/data/b.txt", 1);
+    fs.add_file("/data/a.txt", 500);
+    fs.add_dir("/data/z");
+    fs.add_file("/data/y/big", 1000);
+
+    let (tree, _) = scan(fs, &options());
+
+    assert_eq!(child_names(&tree, "/data"), vec!["y", "z", "a.txt", "b.txt"]);
+}
+
+#[test]
+fn items_under_directories_are_counted() {
+    let (tree, _) = scan(photos(), &options());
+
+    assert_eq!(tree.item(ROOT).item_count, 7);
+    assert_eq!(item(&tree, "/data/photos").item_count, 4);
+    assert_eq!(item(&tree, "/data/photos/2019").item_count, 1);
+    assert_eq!(item(&tree, "/data/notes.txt").item_count, 0);
+}
+
+#[test]
+fn folded_items_are_still_counted() {
+    let mut options = options();
+    options.min_size = 4000;
+
+    let (tree, _) = scan(photos(), &options);
+
+    assert_eq!(tree.item(ROOT).item_count, 7);
+}
+
+#[test]
+fn modification_times_are_kept_when_exported() {
+    let mut fs = photos();
+    fs.set_modified("/data/notes.txt", UNIX_EPOCH + Duration::from_secs(1_500_000_000));
+
+    let (tree, _) = scan(fs, &options());
+    let imported: Tree = serde_json::from_str(&serde_json::to_string(&tree).unwrap()).unwrap();
+
+    assert_eq!(item(&tree, "/data/notes.txt").modified, Some(1_500_000_000));
+    assert_eq!(item(&tree, "/data/photos").modified, None);
+    assert_eq!(item(&imported, "/data/notes.txt").modified, Some(1_500_000_000));
+    assert_eq!(imported.item(ROOT).item_count, 7);
+}
+
+#[test]
+fn the_root_is_read_like_any_other_directory() {
+    let mut fs = photos();
+    fs.set_modified("/data", UNIX_EPOCH + Duration::from_secs(1_500_000_000));
+
+    let (tree, _) = scan(fs, &options());
+
+    assert_eq!(tree.item(ROOT).modified, Some(1_500_000_000));
+    assert_eq!(tree.item(ROOT).size, 18_100);
+}
+
+#[test]
+fn removed_entries_are_taken_off_their_parents() {
+    let (mut tree, _) = scan(photos(), &options());
+
+    tree.remove(tree.find_path(Path::new("/data/photos/2019")).unwrap());
+
+    assert_eq!(child_names(&tree, "/data/photos"), vec!["cat.jpg", "dog.jpg"]);
+    assert_eq!(item(&tree, "/data/photos").size, 8000);
+    assert_eq!(item(&tree, "/data/photos").item_count, 2);
+    assert_eq!(tree.item(ROOT).size, 8100);
+    assert_eq!(tree.item(ROOT).item_count, 5);
+}
+
+#[test]
+fn directories_marked_unread_are_emptied_and_pending() {
+    let (mut tree, _) = scan(photos(), &options());
+
+    tree.mark_unread(tree.find_path(Path::new("/data/photos")).unwrap());
+
+    assert!(child_names(&tree, "/data/photos").is_empty());
+    assert!(item(&tree, "/data/photos").is_pending);
+    assert!(tree.item(ROOT).is_pending);
+    assert_eq!(tree.item(ROOT).size, 100);
+    assert_eq!(tree.item(ROOT).item_count, 3);
+}
+
+#[test]
+fn removing_a_counted_hard_link_counts_the_one_left() {
+    let mut fs = MemoryFileSystem::new();
+    fs.add_file("/data/a/original", 1000);
+    fs.add_hard_link("/data/b/link", "/data/a/original");
+    let (mut tree, _) = scan(fs, &options());
+
+    tree.remove(tree.find_path(Path::new("/data/a/original")).unwrap());
+
+    assert!(!item(&tree, "/data/b/link").is_extra_link);
+    assert_eq!(item(&tree, "/data/a").size, 0);
+    assert_eq!(item(&tree, "/data/b").size, 1000);
+    assert_eq!(tree.item(ROOT).size, 1000);
+}
+
+#[test]
+fn removing_an_extra_hard_link_leaves_the_sizes() {
+    let mut fs = MemoryFileSystem::new();
+    fs.add_file("/data/a/original", 1000);
+    fs.add_hard_link("/data/b/link", "/data/a/original");
+    let (mut tree, _) = scan(fs, &options());
+
+    tree.remove(tree.find_path(Path::new("/data/b")).unwrap());
+
+    assert!(!item(&tree, "/data/a/original").is_extra_link);
+    assert_eq!(tree.item(ROOT).size, 1000);
+    assert_eq!(tree.item(ROOT).item_count, 2);
+}
+
+#[test]
+fn removing_a_directory_removes_everything_in_it() {
+    let fs = photos();
+
+    fs.remove(Path::new("/data/photos")).unwrap();
+    let (tree, _) = scan(fs, &options());
+
+    assert_eq!(child_names(&tree, "/data"), vec!["empty", "notes.txt"]);
+}
+
+#[test]
+fn directories_that_cant_be_read_cant_be_removed() {
+    let mut fs = photos();
+    fs.deny_read_dir("/data/photos/2019");
+
+    assert!(fs.remove(Path::new("/data/photos")).is_err());
+    assert!(fs.metadata(Path::new("/data/photos/cat.jpg")).is_ok());
+}
+
+#[test]
+fn exports_have_the_size_of_the_files_in_each_directory() {
+    let (tree, _) = scan(photos(), &options());
+
+    let exported = serde_json::to_value(&tree).unwrap();
+
+    assert_eq!(exported["files_size"], 100);
+    assert_eq!(exported["children"][1]["name"], "photos");
+    assert_eq!(exported["children"][1]["files_size"], 8000);
+    assert_eq!(exported["children"][1]["files_allocated_size"], 12_288);
+}
+
+#[test]
+fn result_is_the_same_for_any_number_of_threads() {
+    let mut single = options();
+    single.threads = 1;
+    let mut many = options();
+    many.threads = 8;
+
+    let (lhs, _) = scan(photos(), &single);
+    let (rhs, _) = scan(photos(), &many);
+
+    assert_eq!(serde_json::to_string(&lhs).unwrap(), serde_json::to_string(&rhs).unwrap());
+}
+
+#[test]
+fn hard_links_are_counted_once() {
+    let mut fs = MemoryFileSystem::new();
+    fs.add_file("/data/a/original", 1000);
+    fs.add_hard_link("/data/b/link", "/data/a/original");
+
+    let (tree, _) = scan(fs, &options());
+
+    assert!(!item(&tree, "/data/a/original").is_extra_link);
+    assert!(item(&tree, "/data/b/link").is_extra_link);
+    assert_eq!(tree.item(ROOT).size, 1000);
+}
+
+#[test]
+fn extra_links_are_not_among_the_largest_entries() {
+    let mut fs = MemoryFileSystem::new();
+    fs.add_file("/data/a/original", 1000);
+    fs.add_file("/data/b/small", 10);
+    fs.add_hard_link("/data/b/link", "/data/a/original");
+    let (tree, _) = scan(fs, &options());
+
+    let largest: Vec<PathBuf> = largest_at_depth(&tree, 2, 10, SizeMode::Apparent).into_iter()
+        .map(|id| tree.path(id))
+        .collect();
+
+    assert_eq!(largest, vec![PathBuf::from("/data/a/original"), PathBuf::from("/data/b/small")]);
+}
+
+/// Writes the tree in ncdu's format and reads it back
+fn ncdu_round_trip(tree: Tree) -> Tree {
+    let disk = Disk { name: "/data".to_string(), available_space: 0, total_space: 0, tree: Arc::new(tree) };
+    let mut written = vec![];
+    write_ncdu(&disk, &mut written).unwrap();
+    return (*read_ncdu(written.as_slice()).unwrap().tree).clone();
+}
+
+#[test]
+fn hard_links_are_kept_in_ncdu_exports() {
+    let mut fs = MemoryFileSystem::new();
+    fs.add_file("/data/a/original", 1000);
+    fs.add_hard_link("/data/b/link", "/data/a/original");
+    let (tree, _) = scan(fs, &options());
+
+    let imported = ncdu_round_trip(tree.clone());
+
+    let original = tree.find_path(Path::new("/data/a/original")).unwrap();
+    let imported_original = imported.find_path(Path::new("/data/a/original")).unwrap();
+    assert_eq!(imported.hard_link(imported_original), tree.hard_link(original));
+    assert!(!item(&imported, "/data/a/original").is_extra_link);
+    assert!(item(&imported, "/data/b/link").is_extra_link);
+    assert_eq!(imported.item(ROOT).size, 1000);
+}
+
+#[test]
+fn ncdu_entries_inherit_the_device_of_their_directory() {
+    let file = r#"{"name":"file","asize":1000,"ino":5,"hlnkc":true}"#;
+    let json = format!(r#"[1,1,{{}},[{{"name":"/data","dev":1}},[{{"name":"a"}},{}],[{{"name":"b","dev":2}},{}]]]"#, file, file);
+
+    let tree = read_ncdu(json.as_bytes()).unwrap().tree;
+
+    let a = tree.find_path(Path::new("/data/a/file")).unwrap();
+    let b = tree.find_path(Path::new("/data/b/file")).unwrap();
+    assert_eq!(tree.hard_link(a), Some((1, 5)));
+    assert_eq!(tree.hard_link(b), Some((2, 5)));
+    assert!(!tree.item(b).is_extra_link);
+    assert_eq!(tree.item(ROOT).size, 2000);
+}
+
+/// An ncdu export of directories nested `depth` deep with a file at the bottom
+fn nested_ncdu(depth: usize) -> String {
+    let dirs = r#"[{"name":"dir"},"#.repeat(depth);
+    return format!(r#"[1,1,{{}},[{{"name":"/data"}},{}{{"name":"file","asize":10}}{}]]"#, dirs, "]".repeat(depth));
+}
+
+#[test]
+fn deeply_nested_ncdu_exports_are_read_up_to_a_limit() {
+    let tree = read_ncdu(nested_ncdu(MAX_DEPTH - 1).as_bytes()).unwrap().tree;
+    assert_eq!(tree.item(ROOT).size, 10);
+
+    let err = read_ncdu(nested_ncdu(MAX_DEPTH).as_bytes()).err().unwrap();
+    assert!(err.to_string().contains("nested"), "{}", err);
+}
+
+#[test]
+fn ncdu_exports_need_a_supported_version_and_a_root() {
+    assert!(read_ncdu(r#"[2,0,{},[{"name":"/data"}]]"#.as_bytes()).is_err());
+    assert!(read_ncdu(r#"[1,1,{}]"#.as_bytes()).is_err());
+    assert!(read_ncdu(r#"[1,1,{},[]]"#.as_bytes()).is_err());
+    assert_eq!(read_ncdu(r#"[1,2,{"extra":[[]]},{"name":"/data"},{}]"#.as_bytes()).unwrap().name, "/data");
+}
+
+#[test]
+fn folded_entries_are_left_out_of_ncdu_exports() {
+    let mut options = options();
+    options.min_size = 4000;
+    let (tree, _) = scan(photos(), &options);
+
+    let imported = ncdu_round_trip(tree.clone());
+
+    assert_eq!(child_names(&tree, "/data"), vec!["photos", "2 smaller items"]);
+    assert_eq!(child_names(&imported, "/data"), vec!["photos"]);
+    assert_eq!(imported.item(ROOT).size, tree.item(ROOT).size);
+}
+
+#[test]
+fn symlinks_are_counted_as_themselves_by_default() {
+    let mut fs = photos();
+    fs.add_symlink("/data/latest", "photos/cat.jpg");
+    fs.add_symlink("/data/all", "photos");
+
+    let (tree, _) = scan(fs, &options());
+
+    let latest = tree.find_path(Path::new("/data/latest")).unwrap();
+    assert!(tree.item(latest).is_symlink);
+    assert_eq!(tree.item(latest).size, "photos/cat.jpg".len() as u64);
+    assert_eq!(tree.link_target(latest), Some(Path::new("photos/cat.jpg")));
+    assert!(!item(&tree, "/data/all").is_dir);
+    assert_eq!(tree.item(ROOT).size, 18_100 + "photos/cat.jpg".len() as u64 + "photos".len() as u64);
+}
+
+#[test]
+fn followed_symlinks_count_what_they_point_to_once() {
+    let mut fs = MemoryFileSystem::new();
+    fs.add_file("/outside/big", 50_000);
+    fs.add_file("/data/file", 10);
+    fs.add_symlink("/data/first", "/outside");
+    fs.add_symlink("/data/second", "/outside");
+    fs.add_symlink("/data/file_link", "file");
+    let mut options = options();
+    options.follow_symlinks = true;
+
+    let (tree, _) = scan(fs, &options);
+
+    let first = item(&tree, "/data/first");
+    assert!(first.is_dir && first.is_symlink);
+    assert_eq!(first.size, 50_000);
+    assert_eq!(item(&tree, "/data/first/big").size, 50_000);
+    assert!(item(&tree, "/data/second").is_extra_link);
+    assert!(item(&tree, "/data/file_link").is_extra_link);
+    assert_eq!(tree.item(ROOT).size, 50_010);
+}
+
+#[test]
+fn files_reached_through_followed_symlinks_are_not_hard_links() {
+    let mut fs = MemoryFileSystem::new();
+    fs.add_file("/data/file", 10);
+    fs.add_symlink("/data/file_link", "file");
+    let mut options = options();
+    options.follow_symlinks = true;
+    let (tree, _) = scan(fs, &options);
+
+    let imported = ncdu_round_trip(tree.clone());
+
+    for path in &["/data/file", "/data/file_link"] {
+        assert_eq!(tree.hard_link(tree.find_path(Path::new(path)).unwrap()), None);
+        assert_eq!(imported.hard_link(imported.find_path(Path::new(path)).unwrap()), None);
+    }
+    assert!(item(&tree, "/data/file_link").is_extra_link);
+}
+
+#[test]
+fn symlink_loops_are_not_followed() {
+    let mut fs = MemoryFileSystem::new();
+    fs.add_file("/data/dir/file", 10);
+    fs.add_symlink("/data/dir/parent", "..");
+    fs.add_symlink("/data/self", "self");
+    let mut options = options();
+    options.follow_symlinks = true;
+
+    let (tree, _) = scan(fs, &options);
+
+    assert!(item(&tree, "/data/dir/parent").is_extra_link);
+    assert!(!item(&tree, "/data/dir/parent").is_dir);
+    //a link to itself can't be resolved so only the link is counted
+    assert!(!item(&tree, "/data/self").is_extra_link);
+    assert_eq!(tree.item(ROOT).size, 10 + "self".len() as u64);
+}
+
+#[test]
+fn broken_symlinks_count_their_own_size() {
+    let mut fs = MemoryFileSystem::new();
+    fs.add_symlink("/data/broken", "missing");
+    let mut options = options();
+    options.follow_symlinks = true;
+
+    let (tree, _) = scan(fs, &options);
+
+    let broken = item(&tree, "/data/broken");
+    assert!(broken.is_symlink && !broken.is_extra_link);
+    assert_eq!(broken.size, "missing".len() as u64);
+}
+
+#[test]
+fn unreadable_directories_are_recorded_and_the_scan_carries_on() {
+    let mut fs = photos();
+    fs.add_file("/data/private/secret", 1000);
+    fs.deny_read_dir("/data/private");
+
+    let (tree, _) = scan(fs, &options());
+
+    assert!(item(&tree, "/data/private").read_error);
+    assert!(child_names(&tree, "/data/private").is_empty());
+    assert_eq!(tree.item(ROOT).size, 18_100);
+    assert_eq!(tree.errors().len(), 1);
+    assert_eq!(tree.errors()[0].path, PathBuf::from("/data/private"));
+    assert_eq!(tree.errors()[0].action, "read directory");
+}
+
+#[test]
+fn entries_whose_metadata_fails_are_marked() {
+    let mut fs = photos();
+    fs.fail_metadata("/data/photos/dog.jpg");
+
+    let (tree, _) = scan(fs, &options());
+
+    assert!(item(&tree, "/data/photos/dog.jpg").bad_file);
+    assert_eq!(item(&tree, "/data/photos").size, 13_000);
+    assert_eq!(tree.errors().len(), 1);
+    assert_eq!(tree.errors()[0].to_string(), "Unable to read the metadata of /data/photos/dog.jpg: No such file or directory");
+}
+
+#[test]
+fn errors_are_sorted_by_path() {
+    let mut fs = MemoryFileSystem::new();
+    for name in &["d", "b", "c", "a"] {
+        fs.deny_read_dir(Path::new("/data").join(name));
+    }
+
+    let (tree, _) = scan(fs, &options());
+
+    let paths: Vec<PathBuf> = tree.errors().iter().map(|error| error.path.clone()).collect();
+    assert_eq!(paths, vec![PathBuf::from("/data/a"), PathBuf::from("/data/b"), PathBuf::from("/data/c"), PathBuf::from("/data/d")]);
+}
+
+#[test]
+fn mount_points_are_not_read() {
+    let mut fs = photos();
+    fs.add_mount_point("/data/usb");
+    fs.add_file("/data/usb/backup", 1_000_000);
+
+    let (tree, _) = scan(fs, &options());
+
+    assert!(item(&tree, "/data/usb").is_mount_point);
+    assert_eq!(tree.item(ROOT).size, 18_100);
+
+    let mut options = options();
+    options.one_file_system = false;
+    let mut fs = photos();
+    fs.add_mount_point("/data/usb");
+    fs.add_file("/data/usb/backup", 1_000_000);
+
+    let (tree, _) = scan(fs, &options);
+
+    assert_eq!(tree.item(ROOT).size, 1_018_100);
+}
+
+#[test]
+fn excluded_entries_are_not_counted() {
+    let mut options = options();
+    options.filter = ScanFilter::new(&[], &["*.jpg".to_string()]).unwrap();
+
+    let (tree, _) = scan(photos(), &options);
+
+    assert!(item(&tree, "/data/photos/cat.jpg").is_excluded);
+    assert_eq!(tree.item(ROOT).size, 100);
+}
+
+#[test]
+fn ignore_files_mark_entries() {
+    let mut fs = photos();
+    fs.add_text_file("/data/.gitignore", "*.jpg\n!dog.jpg\n");
+    let mut options = options();
+    options.use_ignore_files = true;
+
+    let (tree, _) = scan(fs, &options);
+
+    assert!(item(&tree, "/data/photos/cat.jpg").is_ignored);
+    assert!(!item(&tree, "/data/photos/dog.jpg").is_ignored);
+    assert_eq!(tree.item(ROOT).ignored_size, 13_000);
+}
+
+#[test]
+fn small_entries_are_folded() {
+    let mut options = options();
+    options.min_size = 4000;
+
+    let (tree, _) = scan(photos(), &options);
+
+    assert_eq!(child_names(&tree, "/data/photos"), vec!["2019", "cat.jpg", "dog.jpg"]);
+    assert_eq!(child_names(&tree, "/data"), vec!["photos", "2 smaller items"]);
+    assert_eq!(tree.item(ROOT).size, 18_100);
+}
+
+#[test]
+fn entries_below_max_depth_are_folded() {
+    let mut options = options();
+    options.max_depth = Some(1);
+
+    let (tree, _) = scan(photos(), &options);
+
+    assert_eq!(child_names(&tree, "/data/photos"), vec!["4 smaller items"]);
+    assert_eq!(item(&tree, "/data/photos").size, 18_000);
+}
+
+#[test]
+fn events_start_and_finish_the_scan() {
+    let mut fs = photos();
+    fs.deny_read_dir("/data/private");
+
+    let (_, events) = scan(fs, &options());
+
+    assert!(matches!(events.first(), Some(Status::Started { path }) if path == Path::new("/data")));
+    let summary = match events.last() {
+        Some(Status::Finished(summary)) => summary.clone(),
+        other => panic!("The last event was {:?}", other),
+    };
+    assert_eq!(summary.files, 4);
+    assert_eq!(summary.dirs, 5);
+    assert_eq!(summary.size, 18_100);
+    assert_eq!(summary.errors, 1);
+    assert!(!events.iter().any(|event| matches!(event, Status::Cancelled)));
+}
+
+#[test]
+fn events_report_each_directory_and_error() {
+    let mut fs = photos();
+    fs.deny_read_dir("/data/private");
+
+    let (_, events) = scan(fs, &options());
+
+    let mut entered: Vec<PathBuf> = events.iter()
+        .filter_map(|event| match event {
+            Status::DirEntered { path } => Some(path.clone()),
+            _ => None,
+        })
+        .collect();
+    entered.sort();
+    assert_eq!(entered, ["/data", "/data/empty", "/data/photos", "/data/photos/2019", "/data/private"].iter().map(PathBuf::from).collect::<Vec<_>>());
+
+    let photos_size = events.iter().find_map(|event| match event {
+        Status::DirFinished { path, size, .. } if path == Path::new("/data/photos") => Some(*size),
+        _ => None,
+    });
+    assert_eq!(photos_size, Some(18_000));
+    //the root finishes after everything under it
+    let root_finished = events.iter().position(|event| matches!(event, Status::DirFinished { path, .. } if path == Path::new("/data"))).unwrap();
+    let photos_finished = events.iter().position(|event| matches!(event, Status::DirFinished { path, .. } if path == Path::new("/data/photos"))).unwrap();
+    assert!(photos_finished < root_finished);
+
+    let errors: Vec<&Status> = events.iter().filter(|event| matches!(event, Status::Error(_))).collect();
+    assert_eq!(errors.len(), 1);
+}
+
+#[test]
+fn progress_ends_with_everything_read() {
+    let (_, events) = scan(photos(), &options());
+
+    let last_progress = events.iter()
+        .rev()
+        .find_map(|event| match event {
+            Status::Reading(progress) => Some(progress.clone()),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(last_progress.files, 4);
+    assert_eq!(last_progress.dirs, 4);
+    assert_eq!(last_progress.bytes, 4096 * 7);
+    assert!(last_progress.percentage < 100);
+}
+
+#[test]
+fn cancelled_scans_mark_unread_directories_as_pending() {
+    let (events, receiver) = mpsc::channel();
+    let cancel_token = Arc::new(CancelToken::new());
+    cancel_token.cancel();
+    let scan = LiveScan::with_file_system(PathBuf::from("/data"), &options(), cancel_token, Arc::new(photos()));
+
+    let tree = Inspector::for_directory(options(), events).populate_with(&scan);
+
+    assert!(tree.item(ROOT).is_pending);
+    let events: Vec<Status> = receiver.try_iter().collect();
+    assert!(matches!(events[events.len() - 2], Status::Cancelled));
+    assert!(matches!(events[events.len() - 1], Status::Finished(_)));
+}
+
+#[test]
+fn snapshots_can_only_be_taken_while_scanning() {
+    let (events, _receiver) = mpsc::channel();
+    let scan = LiveScan::with_file_system(PathBuf::from("/data"), &options(), Arc::new(CancelToken::new()), Arc::new(photos()));
+
+    let before = scan.root_snapshot().unwrap();
+    Inspector::for_directory(options(), events).populate_with(&scan);
+
+    assert!(before.item(ROOT).is_pending);
+    assert!(before.children(ROOT).is_empty());
+    assert!(scan.root_snapshot().is_none());
+    assert!(scan.snapshot(&before, ROOT).is_none());
+}